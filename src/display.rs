@@ -1,28 +1,171 @@
 extern crate sdl2;
 use sdl2::render::WindowCanvas;
+use sdl2::render::BlendMode;
 use sdl2::pixels::Color;
 use sdl2::rect::Rect;
 
+use std::time::Duration;
+use std::cmp::Ordering;
+
 use crate::tetris;
+use tetris::pieces::Piece;
+use tetris::mino::Mino;
 use tetris::cfg::Settings;
+use tetris::cfg::Refresh;
+use tetris::cfg::Stack;
+use tetris::leaderboard::board_name;
+use tetris::leaderboard::Standing;
+use tetris::leaderboard::Ranking;
+use tetris::mode::Mode;
 use tetris::Tetris;
+use tetris::Phase;
 
+use crate::ttf::Align;
+use crate::ttf::Text;
+use crate::error::Error;
 use crate::R;
 
 const COLUMNS: usize = 10;
 const ROWS: usize = 20;
 
+/// i drought that fills the drought bar, in shapes
+const DROUGHT_BAR: u32 = 30;
+
+/// how long the callout of a perfect clear stays on screen
+const PERFECT_CLEAR_SECS: f32 = 1.5;
+
+/// how long the board frame stays red after a finesse fault
+const FAULT_SECS: f32 = 0.5;
+
 pub trait Draw {
-    fn draw(&self, game: &Tetris, canvas: &mut WindowCanvas) -> R;
+    fn draw(&self, game: &Tetris, canvas: &mut WindowCanvas, text: &mut Text<'_>) -> R;
     fn update(&mut self, game: &Tetris);
+    /// the practice snapshot under a point of the window, if it's on the timeline
+    fn snapshot_at(&self, game: &Tetris, point: (i32, i32)) -> Option<usize>;
+}
+
+// locked minos of a fading or invisible stack are drawn translucent
+fn alpha(game: &Tetris, mino: &Mino) -> u8 {
+    if !mino.locked {
+        return 255;
+    }
+
+    match game.cfg.stack {
+        Stack::Visible => 255,
+        Stack::Invisible => 0,
+        Stack::Fading(fade) => {
+            let age = game.state.elapsed.saturating_sub(mino.locked_at);
+            let left = 1.0 - age.as_secs_f32() / fade.as_secs_f32().max(f32::EPSILON);
+
+            (left.clamp(0.0, 1.0) * 255.0) as u8
+        },
+    }
+}
+
+fn color([r, g, b]: [u8; 3]) -> Color {
+    Color::RGB(r, g, b)
 }
 
 pub struct TetrisDisplay {
     main: Rect,
+    timer: Rect,
+    /// practice snapshots, below the board
+    timeline: Rect,
+    /// the game against the personal best, where the timeline goes as practice isn't ranked
+    pace: Rect,
+    /// the mode, time, score, level and lines, below the timeline
+    hud: Rect,
     cells: [[Rect; COLUMNS]; ROWS],
 }
 
+// how far above its cells the falling shape is drawn, sliding down between frames
+fn slide(game: &Tetris, cell_height: u32) -> i32 {
+    // drawn once per frame of the game, there's nothing in between to show
+    if let Refresh::Cap(hz) = game.cfg.refresh {
+        if hz as f64 == game.cfg.rules.fps.round() {
+            return 0;
+        }
+    }
+
+    let rows = (game.fallen as usize * game.scale()) as f32 * (1.0 - game.frame_progress());
+    (rows * cell_height as f32) as i32
+}
+
+// a frame `width` pixels wide around the outside of `rect`
+fn draw_frame(canvas: &mut WindowCanvas, rect: Rect, width: i32) -> Result<(), String> {
+    for i in 1..=width {
+        canvas.draw_rect(Rect::new(
+            rect.x() - i,
+            rect.y() - i,
+            rect.width() + 2 * i as u32,
+            rect.height() + 2 * i as u32,
+        ))?;
+    }
+
+    Ok(())
+}
+
+// a vertical bar to the right of the board
+fn timer_rect(main: Rect, window_width: u32) -> Rect {
+    let gap = (window_width as f32 * 0.05) as i32;
+    let width = (window_width as f32 * 0.03) as u32;
+
+    Rect::new(main.right() + gap, main.y(), width.max(1), main.height())
+}
+
+// a horizontal bar below the board
+fn timeline_rect(main: Rect, window_height: u32) -> Rect {
+    let gap = (window_height as f32 * 0.03) as i32;
+    let height = (window_height as f32 * 0.02) as u32;
+
+    Rect::new(main.x(), main.bottom() + gap, main.width(), height.max(1))
+}
+
+// two lines of text below the timeline, as wide as the board
+fn hud_rect(timeline: Rect, window_height: u32) -> Rect {
+    let gap = (window_height as f32 * 0.02) as i32;
+    let height = (window_height as f32 * 0.12) as u32;
+
+    Rect::new(timeline.x(), timeline.bottom() + gap, timeline.width(), height.max(2))
+}
+
+// e.g `1:05.20`
+fn clock(time: Duration) -> String {
+    let centis = time.as_millis() / 10;
+    format!("{}:{:02}.{:02}", centis / 6000, centis / 100 % 60, centis % 100)
+}
+
 impl TetrisDisplay {
+    // a shape in its spawn orientation with its top left at (x, y), returns its height in rows
+    fn draw_shape(
+        &self,
+        canvas: &mut WindowCanvas,
+        piece: &Piece,
+        x: i32,
+        y: i32,
+    ) -> Result<i32, String> {
+        let cell_width = self.main.width() as f32 / COLUMNS as f32;
+        let cell_height = self.main.height() as f32 / ROWS as f32;
+
+        let layout = &piece.layouts[0];
+        let left = layout.iter().map(|p| p[0]).min().unwrap_or(0);
+        let top = layout.iter().map(|p| p[1]).min().unwrap_or(0);
+        let bottom = layout.iter().map(|p| p[1]).max().unwrap_or(0);
+
+        for &[column, row] in layout.iter() {
+            let cell = Rect::new(
+                x + ((column - left) as f32 * cell_width) as i32,
+                y + ((row - top) as f32 * cell_height) as i32,
+                (cell_width * 0.9) as u32,
+                (cell_height * 0.9) as u32,
+            );
+
+            canvas.fill_rect(cell)?;
+        }
+
+        Ok(bottom - top + 1)
+    }
+
     pub fn init(settings: &Settings) -> Self {
         let (window_width, window_height) = settings.window_size;
 
@@ -55,36 +198,575 @@ impl TetrisDisplay {
             cell.center_on((x, y));
         }
 
-        Self { main, cells }
+        let timer = timer_rect(main, window_width);
+        let timeline = timeline_rect(main, window_height);
+        let hud = hud_rect(timeline, window_height);
+
+        Self {
+            main,
+            timer,
+            timeline,
+            pace: timeline,
+            hud,
+            cells,
+        }
     }
 }
 
-impl Draw for TetrisDisplay {
-    fn draw(&self, game: &Tetris, canvas: &mut WindowCanvas) -> R {
+impl TetrisDisplay {
+    // the mode and the time left in timed modes or played otherwise,
+    // then the score, level and lines
+    fn draw_hud(
+        &self,
+        game: &Tetris,
+        canvas: &mut WindowCanvas,
+        text: &mut Text<'_>,
+    ) -> Result<(), String> {
+        let row = self.hud.height() as i32 / 2;
+        let size = (row as f32 * 0.8).max(1.0) as u16;
+        let (left, right) = (self.hud.left(), self.hud.right());
+        let center = self.hud.center().x();
+        let (top, bottom) = (self.hud.top(), self.hud.top() + row);
+
+        let time = match game.remaining_time() {
+            Some(remaining) => clock(remaining),
+            None => clock(game.state.elapsed),
+        };
+
+        let (mode, rules) = (game.cfg.mode.name(), game.cfg.rules.name);
+        let score = &game.state.score;
+
+        let lines = [
+            (format!("{mode} ({rules})"), (left, top), Color::GREY, Align::Left),
+            (time, (right, top), Color::WHITE, Align::Right),
+            (score.points.to_string(), (left, bottom), Color::WHITE, Align::Left),
+            (
+                format!("lv {}", score.level),
+                (center, bottom),
+                Color::WHITE,
+                Align::Center,
+            ),
+            (
+                format!("{} lines", score.lines),
+                (right, bottom),
+                Color::WHITE,
+                Align::Right,
+            ),
+        ];
+
+        for (line, at, color, align) in lines {
+            text.draw(canvas, &line, at, size, color, align)?;
+        }
+
+        Ok(())
+    }
+
+    // the leaderboard over the dimmed board, the result of the game in yellow
+    fn draw_standing(
+        &self,
+        game: &Tetris,
+        canvas: &mut WindowCanvas,
+        text: &mut Text<'_>,
+        standing: &Standing,
+    ) -> Result<(), String> {
+        let Some(ranking) = Ranking::of(&game.cfg.mode) else {
+            return Ok(());
+        };
+
+        let cell_height = self.main.height() as f32 / ROWS as f32;
+        let size = (cell_height * 0.6).max(1.0) as u16;
+        let line = text.size_of("0", size)?.1 as i32;
+        let margin = cell_height as i32 / 2;
+        let (left, right) = (self.main.left() + margin, self.main.right() - margin);
+        let center = self.main.center().x();
+        let mut y = self.main.top() + margin;
+
+        let title = match standing.rank {
+            Some(0) => "new record!".to_string(),
+            Some(rank) => format!("#{} on the leaderboard", rank + 1),
+            None => "leaderboard".to_string(),
+        };
+
+        text.draw(canvas, &title, (center, y), size, Color::YELLOW, Align::Center)?;
+        y += line;
+
+        let board = format!("{} ({})", board_name(&game.cfg), game.cfg.rules.name);
+        text.draw(canvas, &board, (center, y), size, Color::GREY, Align::Center)?;
+        y += line * 3 / 2;
+
+        for (i, entry) in standing.board.iter().enumerate() {
+            let ours = standing.rank == Some(i);
+
+            let name = match (&standing.naming, ours) {
+                (Some(typed), true) => format!("{typed}_"),
+                _ => entry.name.clone(),
+            };
+
+            let color = match ours {
+                true => Color::YELLOW,
+                false => Color::WHITE,
+            };
+
+            let place = format!("{:>2}. {name}", i + 1);
+
+            text.draw(canvas, &place, (left, y), size, color, Align::Left)?;
+            text.draw(canvas, &ranking.value(entry), (right, y), size, color, Align::Right)?;
+            y += line;
+        }
+
+        if standing.naming.is_some() {
+            let hint = "type a name, enter keeps it";
+
+            y += line / 2;
+            text.draw(canvas, hint, (center, y), size, Color::GREY, Align::Center)?;
+        }
+
+        Ok(())
+    }
+
+    // along the bottom of the board, the seed of a game that's over and what the keys
+    // besides playing do, but not while they type a name
+    fn draw_hints(
+        &self,
+        game: &Tetris,
+        canvas: &mut WindowCanvas,
+        text: &mut Text<'_>,
+    ) -> Result<(), String> {
+        let naming = game
+            .standing
+            .as_ref()
+            .map(|s| s.naming.is_some())
+            .unwrap_or(false);
+        let mut hints = Vec::new();
+
+        if game.is_over() && game.playback.is_none() {
+            hints.push((format!("seed {}", game.seed), Color::GREY));
+
+            if !naming {
+                hints.push(("r plays this seed again".to_string(), Color::WHITE));
+            }
+        }
+
+        if game.offers_resume() && !naming {
+            hints.push(("l picks up the game quit halfway".to_string(), Color::YELLOW));
+        }
+
+        if hints.is_empty() {
+            return Ok(());
+        }
+
+        let cell_height = self.main.height() as f32 / ROWS as f32;
+        let size = (cell_height * 0.6).max(1.0) as u16;
+        let line = text.size_of("0", size)?.1 as i32;
+        let margin = cell_height as i32 / 2;
+        let top = self.main.bottom() - margin * 2 - line * hints.len() as i32;
+
+        canvas.set_blend_mode(BlendMode::Blend);
+        canvas.set_draw_color(Color::RGBA(0, 0, 0, 160));
+        canvas.fill_rect(Rect::new(
+            self.main.x(),
+            top,
+            self.main.width(),
+            (self.main.bottom() - top) as u32,
+        ))?;
+        canvas.set_blend_mode(BlendMode::None);
+
+        for (i, (hint, color)) in hints.iter().enumerate() {
+            let at = (self.main.center().x(), top + margin + line * i as i32);
+            text.draw(canvas, hint, at, size, *color, Align::Center)?;
+        }
+
+        Ok(())
+    }
+
+    // a line for each of the stats, as many as fit
+    fn draw_stats(
+        &self,
+        game: &Tetris,
+        canvas: &mut WindowCanvas,
+        text: &mut Text<'_>,
+        rect: Rect,
+    ) -> Result<(), String> {
+        let cell_height = self.main.height() as f32 / ROWS as f32;
+        let size = (cell_height * 0.55).max(1.0) as u16;
+        let line = text.size_of("0", size)?.1 as i32;
+        let mut y = rect.y();
+
+        for (label, value) in game.state.stats.panel(game.state.elapsed) {
+            if y + line > rect.bottom() {
+                break;
+            }
+
+            text.draw(canvas, &label, (rect.left(), y), size, Color::GREY, Align::Left)?;
+            text.draw(canvas, &value, (rect.right(), y), size, Color::WHITE, Align::Right)?;
+
+            y += line;
+        }
+
+        Ok(())
+    }
+
+    // the errors of sdl are strings, they're told apart in `Draw::draw`
+    fn draw_game(
+        &self,
+        game: &Tetris,
+        canvas: &mut WindowCanvas,
+        text: &mut Text<'_>,
+    ) -> Result<(), String> {
         canvas.set_draw_color(Color::BLACK);
         canvas.clear();
 
         canvas.set_draw_color(Color::GREY);
         canvas.draw_rect(self.main)?;
 
+        // how far along the line clear animation is, from 0.0 to 1.0
+        let cleared = match game.state.phase {
+            Phase::LineClear(left) => {
+                let total = game.timings().line_clear.max(1);
+                Some((left, 1.0 - left as f32 / total as f32))
+            },
+            _ => None,
+        };
+
+        let slide = slide(game, self.cells[0][0].height());
+
         for i in 0..COLUMNS * ROWS {
             let column = i % COLUMNS;
             let row = i / COLUMNS;
 
-            let cell = self.cells[row][column];
+            let mut cell = self.cells[row][column];
+
+            // cleared rows flash and dissolve from the middle outwards
+            if let (Some(mino), Some((left, progress))) = (game.state.minos[row][column], cleared) {
+                if game.state.clearing.contains(&row) {
+                    let distance = (column as f32 + 0.5 - COLUMNS as f32 / 2.0).abs();
 
-            if game.minos[row][column].is_none() {
+                    if distance < progress * COLUMNS as f32 / 2.0 {
+                        canvas.set_draw_color(Color::GRAY);
+                        canvas.draw_rect(cell)?;
+                        continue;
+                    }
+
+                    match (left / 4) % 2 {
+                        0 => canvas.set_draw_color(Color::WHITE),
+                        _ => canvas.set_draw_color(color(mino.color)),
+                    }
+
+                    canvas.fill_rect(cell)?;
+                    continue;
+                }
+            }
+
+            let Some(mino) = game.state.minos[row][column] else {
                 canvas.set_draw_color(Color::GRAY);
                 canvas.draw_rect(cell)?;
-            } else {
-                canvas.set_draw_color(Color::BLUE);
-                canvas.fill_rect(cell)?;
+                continue;
+            };
+
+            let color = color(mino.color);
+            let alpha = alpha(game, &mino);
+
+            // a faded or sliding mino leaves its empty cell behind
+            if alpha < 255 || (!mino.locked && slide > 0) {
+                canvas.set_draw_color(Color::GRAY);
+                canvas.draw_rect(cell)?;
+            }
+
+            if !mino.locked {
+                cell.offset(0, -slide);
+            }
+
+            canvas.set_blend_mode(BlendMode::Blend);
+            canvas.set_draw_color(Color::RGBA(color.r, color.g, color.b, alpha));
+            canvas.fill_rect(cell)?;
+            canvas.set_blend_mode(BlendMode::None);
+        }
+
+        // where the drill wants the shape
+        canvas.set_draw_color(Color::WHITE);
+
+        for [column, row] in game.target_cells() {
+            canvas.draw_rect(self.cells[row][column])?;
+        }
+
+        // edges between the stack and empty cells
+        if game.cfg.outline {
+            let is_locked = |c: i32, r: i32| -> bool {
+                (0..COLUMNS as i32).contains(&c)
+                    && (0..ROWS as i32).contains(&r)
+                    && game.state.minos[r as usize][c as usize]
+                        .map(|m| m.locked)
+                        .unwrap_or(false)
+            };
+
+            canvas.set_draw_color(Color::WHITE);
+
+            for i in 0..COLUMNS * ROWS {
+                let (column, row) = ((i % COLUMNS) as i32, (i / COLUMNS) as i32);
+                let cell = self.cells[row as usize][column as usize];
+
+                if !is_locked(column, row) || game.state.clearing.contains(&(row as usize)) {
+                    continue;
+                }
+
+                let (left, top) = (cell.left(), cell.top());
+                let (right, bottom) = (cell.right(), cell.bottom());
+
+                if row > 0 && !is_locked(column, row - 1) {
+                    canvas.draw_line((left, top), (right, top))?;
+                }
+
+                if row + 1 < ROWS as i32 && !is_locked(column, row + 1) {
+                    canvas.draw_line((left, bottom), (right, bottom))?;
+                }
+
+                if column > 0 && !is_locked(column - 1, row) {
+                    canvas.draw_line((left, top), (left, bottom))?;
+                }
+
+                if column + 1 < COLUMNS as i32 && !is_locked(column + 1, row) {
+                    canvas.draw_line((right, top), (right, bottom))?;
+                }
+            }
+        }
+
+        // countdown of timed modes, drains from the top
+        if let (Some(limit), Some(remaining)) = (game.cfg.mode.time_limit(), game.remaining_time())
+        {
+            let ratio = remaining.as_secs_f32() / limit.as_secs_f32();
+            let height = (self.timer.height() as f32 * ratio) as u32;
+            let mut left = self.timer;
+
+            left.set_height(height);
+            left.set_y(self.timer.bottom() - height as i32);
+
+            canvas.set_draw_color(Color::GREY);
+            canvas.draw_rect(self.timer)?;
+
+            if height > 0 {
+                canvas.set_draw_color(Color::YELLOW);
+                canvas.fill_rect(left)?;
             }
         }
 
+        // a notch for every shape locked in practice, filled up to the one played from
+        if let Some(practice) = &game.practice {
+            let count = practice.snapshots.len() as i32;
+            let width = self.timeline.width() as i32;
+
+            canvas.set_draw_color(Color::GREY);
+            canvas.draw_rect(self.timeline)?;
+
+            let mut played = self.timeline;
+            played.set_width((width * practice.at as i32 / count.max(1)) as u32);
+
+            canvas.set_draw_color(Color::WHITE);
+            canvas.fill_rect(played)?;
+
+            for i in 1..count {
+                let x = self.timeline.x() + width * i / count;
+
+                canvas.set_draw_color(Color::GREY);
+                canvas.draw_line((x, self.timeline.y()), (x, self.timeline.bottom()))?;
+            }
+
+            let at = self.timeline.x() + width * practice.at as i32 / count.max(1);
+
+            canvas.set_draw_color(Color::YELLOW);
+            canvas.fill_rect(Rect::new(
+                at,
+                self.timeline.y(),
+                (width / count.max(1)).max(2) as u32,
+                self.timeline.height(),
+            ))?;
+        }
+
+        // filled as far as the game got towards the personal best, gold once it's beaten
+        // in points and red once it took more pieces or time. a sprint fills up with its
+        // lines instead, as its best is a time
+        if let (Some(best), Some(ranking)) = (&game.best, Ranking::of(&game.cfg.mode)) {
+            let millis = game.state.elapsed.as_millis() as u64;
+
+            let (value, target) = match (ranking, &game.cfg.mode) {
+                (Ranking::Points, _) => (game.state.score.points, best.points),
+                (Ranking::Pieces, _) => (game.state.stats.pieces, best.pieces),
+                (Ranking::Time, Mode::Sprint { lines }) => (game.state.score.lines, *lines),
+                (Ranking::Time, _) => (0, 1),
+            };
+
+            let width = self.pace.width() as f32;
+            let ratio = (value as f32 / target.max(1) as f32).min(1.0);
+
+            let mut filled = self.pace;
+            filled.set_width((width * ratio) as u32);
+
+            canvas.set_draw_color(match (ranking, value.cmp(&target)) {
+                (Ranking::Points, Ordering::Less) => Color::WHITE,
+                (Ranking::Points, _) => Color::RGB(255, 200, 0),
+                (Ranking::Pieces, Ordering::Greater) => Color::RED,
+                (Ranking::Pieces, _) => Color::WHITE,
+                (Ranking::Time, _) if millis > best.millis => Color::RED,
+                (Ranking::Time, _) => Color::WHITE,
+            });
+
+            if filled.width() > 0 {
+                canvas.fill_rect(filled)?;
+            }
+
+            canvas.set_draw_color(Color::GREY);
+            canvas.draw_rect(self.pace)?;
+
+            // how far the best was at this point of a timed game or a sprint, if played evenly
+            let pace = match (game.cfg.mode.time_limit(), ranking) {
+                (Some(limit), _) => Some(game.state.elapsed.as_secs_f32() / limit.as_secs_f32()),
+                (None, Ranking::Time) => Some(millis as f32 / best.millis.max(1) as f32),
+                (None, _) => None,
+            };
+
+            if let Some(pace) = pace {
+                let x = self.pace.x() + (width * pace.min(1.0)) as i32;
+
+                canvas.set_draw_color(Color::YELLOW);
+                canvas.draw_line((x, self.pace.y()), (x, self.pace.bottom()))?;
+            }
+        }
+
+        // upcoming shapes, then the held one, right of the timer
+        let cell_height = self.main.height() as i32 / ROWS as i32;
+        let x = self.timer.right() + self.timer.width() as i32;
+        let mut y = self.main.y();
+
+        for &variant in game.upcoming() {
+            let piece = &game.cfg.pieces[variant];
+
+            canvas.set_draw_color(color(piece.color));
+            y += cell_height * (self.draw_shape(canvas, piece, x, y)? + 1);
+        }
+
+        if let Some(variant) = game.state.hold {
+            let piece = &game.cfg.pieces[variant];
+
+            match game.state.can_hold {
+                true => canvas.set_draw_color(color(piece.color)),
+                false => canvas.set_draw_color(Color::GRAY),
+            }
+
+            y += cell_height * (self.draw_shape(canvas, piece, x, y + cell_height)? + 2);
+        }
+
+        // stats panel under the hold down to the bottom of the window, bars of the shapes
+        // locked so far and of the current i drought, then the numbers
+        let (window_width, window_height) = game.cfg.window_size;
+        let panel = Rect::new(
+            x,
+            y,
+            (window_width as i32 - x - cell_height).max(1) as u32,
+            (window_height as i32 - y - cell_height).max(ROWS as i32) as u32,
+        );
+
+        let stats = &game.state.stats;
+        let bar_height = (cell_height * 4 / (stats.piece_counts.len() as i32 + 2)).max(2);
+        let most = stats.piece_counts.iter().map(|&(_, count)| count).max();
+        let mut y = panel.y();
+
+        for (variant, &(_, count)) in stats.piece_counts.iter().enumerate() {
+            let width = panel.width() as f32 * count as f32 / most.unwrap_or(1).max(1) as f32;
+
+            canvas.set_draw_color(color(game.cfg.pieces.pieces[variant].color));
+            canvas.fill_rect(Rect::new(
+                panel.x(),
+                y,
+                (width as u32).max(1),
+                (bar_height - 1).max(1) as u32,
+            ))?;
+
+            y += bar_height;
+        }
+
+        // full width at a drought of DROUGHT_BAR shapes, the longest one so far in grey
+        let drought_width = |drought: u32| {
+            let ratio = drought.min(DROUGHT_BAR) as f32 / DROUGHT_BAR as f32;
+            ((panel.width() as f32 * ratio) as u32).max(1)
+        };
+
+        let drought = Rect::new(panel.x(), y + bar_height, 1, bar_height.max(1) as u32);
+
+        canvas.set_draw_color(Color::GRAY);
+        canvas.draw_rect(Rect::new(
+            drought.x(),
+            drought.y(),
+            drought_width(stats.max_drought),
+            drought.height(),
+        ))?;
+        canvas.set_draw_color(Color::RED);
+        canvas.fill_rect(Rect::new(
+            drought.x(),
+            drought.y(),
+            drought_width(stats.drought),
+            drought.height(),
+        ))?;
+
+        let top = drought.bottom() + bar_height;
+        let numbers =
+            Rect::new(panel.x(), top, panel.width(), (panel.bottom() - top).max(1) as u32);
+
+        self.draw_stats(game, canvas, text, numbers)?;
+
+        // a perfect clear lights the board up in gold for a moment
+        if let Some(at) = game.state.perfect_clear_at {
+            let age = game.state.elapsed.saturating_sub(at).as_secs_f32();
+
+            if age < PERFECT_CLEAR_SECS {
+                let left = 1.0 - age / PERFECT_CLEAR_SECS;
+
+                canvas.set_blend_mode(BlendMode::Blend);
+                canvas.set_draw_color(Color::RGBA(255, 200, 0, (left * 96.0) as u8));
+                canvas.fill_rect(self.main)?;
+                canvas.set_draw_color(Color::RGBA(255, 200, 0, (left * 255.0) as u8));
+                draw_frame(canvas, self.main, 4)?;
+                canvas.set_blend_mode(BlendMode::None);
+            }
+        }
+
+        // and a finesse fault turns its frame red
+        if let Some(at) = game.state.fault_at {
+            let age = game.state.elapsed.saturating_sub(at).as_secs_f32();
+
+            if age < FAULT_SECS {
+                let left = 1.0 - age / FAULT_SECS;
+
+                canvas.set_blend_mode(BlendMode::Blend);
+                canvas.set_draw_color(Color::RGBA(255, 0, 0, (left * 255.0) as u8));
+                draw_frame(canvas, self.main, 2)?;
+                canvas.set_blend_mode(BlendMode::None);
+            }
+        }
+
+        // dim the frozen board once the game is over
+        if game.is_over() {
+            canvas.set_blend_mode(BlendMode::Blend);
+            canvas.set_draw_color(Color::RGBA(0, 0, 0, 160));
+            canvas.fill_rect(self.main)?;
+            canvas.set_blend_mode(BlendMode::None);
+        }
+
+        if let Some(standing) = &game.standing {
+            self.draw_standing(game, canvas, text, standing)?;
+        }
+
+        self.draw_hints(game, canvas, text)?;
+
+        self.draw_hud(game, canvas, text)?;
+
         canvas.present();
         Ok(())
     }
+}
+
+impl Draw for TetrisDisplay {
+    fn draw(&self, game: &Tetris, canvas: &mut WindowCanvas, text: &mut Text<'_>) -> R {
+        self.draw_game(game, canvas, text).map_err(Error::Render)
+    }
 
     fn update(&mut self, game: &Tetris) {
         let (window_width, window_height) = game.cfg.window_size;
@@ -97,6 +779,11 @@ impl Draw for TetrisDisplay {
         self.main
             .center_on((pwidth(0.3) as i32, pheight(0.4) as i32));
 
+        self.timer = timer_rect(self.main, window_width);
+        self.timeline = timeline_rect(self.main, window_height);
+        self.pace = self.timeline;
+        self.hud = hud_rect(self.timeline, window_height);
+
         let cell_width = self.main.width() as f32 / 10.0;
         let cell_height = self.main.height() as f32 / 20.0;
         let cell_center = (cell_width / 2.0, cell_height / 2.0);
@@ -116,4 +803,17 @@ impl Draw for TetrisDisplay {
             ));
         }
     }
+
+    fn snapshot_at(&self, game: &Tetris, (x, y): (i32, i32)) -> Option<usize> {
+        let practice = game.practice.as_ref()?;
+
+        if !self.timeline.contains_point((x, y)) {
+            return None;
+        }
+
+        let count = practice.snapshots.len() as i32;
+        let offset = (x - self.timeline.x()) * count / self.timeline.width().max(1) as i32;
+
+        Some(offset.clamp(0, count - 1) as usize)
+    }
 }
@@ -0,0 +1,48 @@
+use std::error;
+use std::fmt;
+use std::io;
+
+#[derive(Debug)]
+pub enum Error {
+    /// sdl, the window or the canvas couldn't start
+    Sdl(String),
+    /// sdl couldn't draw a frame
+    Render(String),
+    /// invalid arguments, or an invalid piece set, puzzle, replay or leaderboard
+    Config(String),
+    /// a file or the terminal couldn't be read or written
+    Io { what: String, error: io::Error },
+    /// the game got into a state it should never be in
+    Engine(String),
+}
+
+pub type R<T = ()> = Result<T, Error>;
+
+impl Error {
+    /// for `map_err`, e.g `fs::read(path).map_err(Error::io("Couldn't read the file"))`
+    pub fn io(what: impl Into<String>) -> impl FnOnce(io::Error) -> Self {
+        let what = what.into();
+        move |error| Error::Io { what, error }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Sdl(e) => write!(f, "Couldn't start SDL: {e}"),
+            Error::Render(e) => write!(f, "Couldn't draw the game: {e}"),
+            Error::Config(e) => write!(f, "{e}"),
+            Error::Io { what, error } => write!(f, "{what}: {error}"),
+            Error::Engine(e) => write!(f, "Something went wrong in the game: {e}"),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Error::Io { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
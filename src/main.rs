@@ -5,73 +5,370 @@ use sdl2::event::WindowEvent;
 use sdl2::event::Event;
 
 mod tetris;
+use tetris::replay::Verdict;
+use tetris::replay::Replay;
+use tetris::replay::Check;
+use tetris::state::SAVE;
+use tetris::leaderboard::NAME_LENGTH;
+use tetris::leaderboard::KEPT;
+use tetris::leaderboard::Leaderboard;
+use tetris::leaderboard::board_name;
+use tetris::leaderboard::clean_name;
+use tetris::leaderboard::Standing;
+use tetris::leaderboard::Entry;
+use tetris::cfg::Settings;
+use tetris::cfg::Refresh;
 use tetris::Tetris;
+use tetris::Input;
 
 pub mod message;
 use message::MinoDirection;
 use message::has_elapsed;
 use message::Messenger;
+use message::Held;
 use message::Rotation;
 use message::Command;
 
 pub mod display;
 
-use std::time::SystemTime;
+pub mod ttf;
+use ttf::Text;
+
+pub mod error;
+use error::Error;
+use error::R;
+
 use std::time::Duration;
+use std::time::Instant;
+use std::path::Path;
+use std::process;
 use std::thread;
+use std::fs;
+
+/// longest wait for input while the game is idle
+const IDLE_WAIT_MS: u32 = 100;
+
+// errors are printed as they read, not as the `Debug` that returning them from main prints
+fn main() {
+    if let Err(err) = run() {
+        eprintln!("{err}");
+        process::exit(1);
+    }
+}
+
+fn run() -> R {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    // `tetris verify replays/ultra-1700000000.txt` checks a replay without a window
+    if let [command, path] = args.as_slice() {
+        if command == "verify" {
+            return verify(path);
+        }
+    }
 
-type R = Result<(), String>;
+    // `tetris leaderboard` shows the best results of every mode and rules
+    if let [command] = args.as_slice() {
+        if command == "leaderboard" {
+            print!("{}", Leaderboard::load()?);
+            return Ok(());
+        }
+    }
+
+    let sdl_context = sdl2::init().map_err(Error::Sdl)?;
+    let video_subsystem = sdl_context.video().map_err(Error::Sdl)?;
 
-fn main() -> R {
-    let sdl_context = sdl2::init()?;
-    let video_subsystem = sdl_context.video()?;
+    // names for the leaderboard are typed into the window
+    video_subsystem.text_input().start();
+
+    let mut game = start(args)?;
+    load_best(&mut game);
 
-    let mut game = Tetris::default();
     let (width, height) = game.cfg.window_size;
 
     let window = video_subsystem
         .window("tetris", width, height)
         .position_centered()
         .build()
-        .expect("Couldn't initialize window.");
+        .map_err(|e| Error::Sdl(format!("Couldn't initialize window: {e}")))?;
 
     // handles input
     let mut messenger = Messenger::default();
 
-    let mut canvas = window
-        .into_canvas()
-        .build()
-        .expect("Couldn't initialize canvas.");
+    // vsync makes presenting wait for the screen
+    let mut canvas = match game.cfg.refresh {
+        Refresh::Vsync => window.into_canvas().present_vsync(),
+        _ => window.into_canvas(),
+    }
+    .build()
+    .map_err(|e| Error::Sdl(format!("Couldn't initialize canvas: {e}")))?;
+
+    let mut event_pump = sdl_context.event_pump().map_err(Error::Sdl)?;
+
+    // fonts and the textures of their glyphs borrow these
+    let ttf = sdl2::ttf::init().map_err(|e| Error::Sdl(format!("Couldn't start ttf: {e}")))?;
+    let creator = canvas.texture_creator();
+    let mut text = Text::new(&ttf, &creator, game.cfg.font.as_deref());
 
-    let mut event_pump = sdl_context.event_pump()?;
+    // the error last shown, so one that keeps happening isn't printed every frame
+    let mut shown: Option<String> = None;
 
-    // traditional game loop
+    // when the next frame is drawn, with a frame cap
+    let mut next_frame = Instant::now();
+
+    // the game runs at a fixed rate in `Tetris::update`, however often it's drawn
     loop {
+        // nothing moves while idle, so wait for input rather than drawing the same frame.
+        // it times out to keep counting how long a key is held down
+        if game.is_idle() {
+            if let Some(event) = event_pump.wait_event_timeout(IDLE_WAIT_MS) {
+                listen(&mut messenger, event);
+            }
+        }
+
         // convert input to a command
         for event in event_pump.poll_iter() {
             listen(&mut messenger, event);
         }
 
         for (key, timestamp) in messenger.onhold.iter_mut() {
-            hold_key(&mut messenger.commands, key, timestamp);
+            hold_key(&mut messenger.commands, key, timestamp, game.das());
         }
 
-        // update game data/info
-        if let Err(err) = update(&mut messenger, &mut game, &canvas) {
-            eprintln!("Encountered error while updating game:\n{err:?}");
-        }
+        game.hold_keys(held_keys(&messenger));
+
+        // update game data/info, then render display based on the info
+        let result = match update(&mut messenger, &mut game, &canvas) {
+            Ok(true) => render(&mut canvas, &game, &mut text),
+            Ok(false) => break,
+            Err(err) => Err(err),
+        };
+
+        show_error(&mut canvas, &mut shown, result.err());
+
+        if let Refresh::Cap(hz) = game.cfg.refresh {
+            next_frame += Duration::from_secs_f64(1.0 / hz as f64);
 
-        // render display based on the info
-        if let Err(err) = render(&mut canvas, &game) {
-            eprintln!("Encountered error while rendering canvas:\n{err:?}");
+            // running late, so start over from now instead of rushing the next frames
+            match next_frame.checked_duration_since(Instant::now()) {
+                Some(wait) => thread::sleep(wait),
+                None => next_frame = Instant::now(),
+            }
         }
+    }
+
+    Ok(())
+}
+
+// a new game, `tetris resume` to pick up the one quit halfway, or
+// `tetris --replay replays/ultra-1700000000.txt` to watch a saved one
+fn start(args: Vec<String>) -> R<Tetris> {
+    match args.as_slice() {
+        [command] if command == "resume" => {
+            let game = Tetris::resume(SAVE)?;
+
+            // a game is only picked up once
+            fs::remove_file(SAVE).map_err(Error::io(format!("Couldn't remove {SAVE}")))?;
+
+            Ok(game)
+        },
+        [flag, path] if flag == "--replay" => {
+            let replay = Replay::load(path)?;
+            let settings = Settings::from_args(replay.args.iter().cloned())?;
+
+            println!(
+                "space pauses, up and down change the speed, and period steps a frame while paused"
+            );
+
+            Ok(Tetris::playback(settings, replay))
+        },
+        _ => {
+            let mut game = Tetris::new(Settings::from_args(args.into_iter())?);
+            game.resumable = Path::new(SAVE).exists();
+
+            if game.resumable {
+                println!("a game was quit halfway, l or `tetris resume` picks it up");
+            }
+
+            Ok(game)
+        },
+    }
+}
+
+// a game quit halfway is kept to be resumed
+fn save_game(game: &Tetris) {
+    if game.playback.is_some() || game.is_over() || game.state.tick == 0 {
+        return;
+    }
+
+    match game.save(SAVE) {
+        Ok(()) => println!("game saved, `tetris resume` picks it up"),
+        Err(err) => eprintln!("{err}"),
+    }
+}
+
+fn verify(path: &str) -> R {
+    match Replay::load(path)?.verify()? {
+        Verdict::Valid(end) => {
+            println!("valid: {end}");
+            Ok(())
+        },
+        Verdict::Diverged {
+            tick,
+            recorded,
+            simulated,
+        } => {
+            let describe = |check: Option<Check>| match check {
+                Some(check) => check.to_string(),
+                None => "nothing".to_string(),
+            };
 
-        // 30 fps
-        thread::sleep(Duration::new(0, 1_000_000_000u32 / 30));
+            Err(Error::Config(format!(
+                "Diverged at frame {tick}: recorded {}, simulated {}.",
+                describe(recorded),
+                describe(simulated)
+            )))
+        },
+    }
+}
+
+// the personal best to pace against, not while watching a replay
+fn load_best(game: &mut Tetris) {
+    if game.playback.is_some() {
+        return;
     }
 
-    // unreachable expression
-    // Ok(())
+    match Leaderboard::load() {
+        Ok(leaderboard) => {
+            let board = leaderboard.board(&board_name(&game.cfg), game.cfg.rules.name);
+            game.best = board.first().cloned();
+        },
+        Err(err) => eprintln!("{err}"),
+    }
+}
+
+// the same settings and seed as the game that ended, to race the same shapes
+fn play_again(game: &mut Tetris, canvas: &WindowCanvas) -> R {
+    let settings = Settings::from_args(game.cfg.args.iter().cloned())?;
+
+    *game = Tetris::with_seed(settings, game.seed);
+    game.resumable = Path::new(SAVE).exists();
+    game.update_scale(canvas)?;
+    load_best(game);
+
+    Ok(())
+}
+
+// the game quit halfway in place of this one
+fn resume(game: &mut Tetris, canvas: &WindowCanvas) -> R {
+    *game = Tetris::resume(SAVE)?;
+
+    // a game is only picked up once
+    fs::remove_file(SAVE).map_err(Error::io(format!("Couldn't remove {SAVE}")))?;
+
+    game.update_scale(canvas)?;
+    load_best(game);
+
+    Ok(())
+}
+
+// the board of a ranked mode is shown once the game is over, a result that makes it
+// is kept right away with `--name`, or once a name is typed in the window
+fn record(game: &mut Tetris) -> R {
+    let Some(entry) = Entry::of(game, "") else {
+        return Ok(());
+    };
+
+    let mut leaderboard = Leaderboard::load()?;
+    let rank = leaderboard.rank(game, &entry);
+
+    let naming = match (rank, &game.cfg.name) {
+        (Some(_), Some(name)) => {
+            let entry = Entry {
+                name: clean_name(name),
+                ..entry.clone()
+            };
+
+            leaderboard.insert(game, entry);
+            leaderboard.save()?;
+            None
+        },
+        (Some(_), None) => Some(String::new()),
+        (None, _) => None,
+    };
+
+    let mut board = leaderboard
+        .board(&board_name(&game.cfg), game.cfg.rules.name)
+        .to_vec();
+
+    // shown in its place while the name is typed
+    if let (Some(rank), Some(_)) = (rank, &naming) {
+        board.insert(rank, entry);
+        board.truncate(KEPT);
+    }
+
+    game.standing = Some(Standing {
+        board,
+        rank,
+        naming,
+    });
+
+    Ok(())
+}
+
+// keep the result under the name typed, `player` if there's none
+fn submit_name(game: &mut Tetris) -> R {
+    let Some(standing) = game.standing.as_mut() else {
+        return Ok(());
+    };
+
+    let (Some(rank), Some(name)) = (standing.rank, standing.naming.take()) else {
+        return Ok(());
+    };
+
+    let entry = &mut standing.board[rank];
+    entry.name = clean_name(&name);
+
+    let entry = entry.clone();
+    let mut leaderboard = Leaderboard::load()?;
+
+    leaderboard.insert(game, entry);
+    leaderboard.save()
+}
+
+fn type_name(game: &mut Tetris, cmd: Command) -> R {
+    let Some(name) = game.standing.as_mut().and_then(|s| s.naming.as_mut()) else {
+        return Ok(());
+    };
+
+    match cmd {
+        Command::Type(c) if !c.is_control() && name.chars().count() < NAME_LENGTH => name.push(c),
+        Command::Erase => {
+            name.pop();
+        },
+        Command::Submit => submit_name(game)?,
+        _ => (),
+    }
+
+    Ok(())
+}
+
+fn is_naming(game: &Tetris) -> bool {
+    game.standing
+        .as_ref()
+        .map(|s| s.naming.is_some())
+        .unwrap_or(false)
+}
+
+// every game played is kept, but not the ones watched
+fn save_replay(game: &Tetris) {
+    if game.playback.is_some() || game.state.tick == 0 {
+        return;
+    }
+
+    match game.replay().save(game.cfg.mode.name()) {
+        Ok(path) => println!("replay saved to {}", path.display()),
+        Err(err) => eprintln!("{err}"),
+    }
 }
 
 fn listen(messenger: &mut Messenger, event: Event) {
@@ -79,13 +376,28 @@ fn listen(messenger: &mut Messenger, event: Event) {
         // Ctrl + C in the terminal
         Event::Quit { timestamp: _ } => messenger.commands.push(Command::Quit),
         // releasing a key
-        Event::KeyUp { keycode, .. } => messenger.key_release(keycode.unwrap()),
+        Event::KeyUp {
+            keycode: Some(keycode),
+            ..
+        } => messenger.key_release(keycode),
         // holding a key
-        Event::KeyDown { keycode, .. } => {
-            press_key(messenger, &keycode.unwrap());
-            messenger.key_hold(keycode.unwrap());
+        Event::KeyDown {
+            keycode: Some(keycode),
+            ..
+        } => {
+            press_key(messenger, &keycode);
+            messenger.key_hold(keycode);
         },
 
+        // the name for the leaderboard, as the keyboard layout writes it
+        Event::TextInput { text, .. } => messenger.commands.extend(text.chars().map(Command::Type)),
+
+        // clicking or dragging along the practice timeline
+        Event::MouseButtonDown { x, y, .. } => messenger.commands.push(Command::Scrub(x, y)),
+        Event::MouseMotion {
+            mousestate, x, y, ..
+        } if mousestate.left() => messenger.commands.push(Command::Scrub(x, y)),
+
         // if you resize the window
         Event::Window {
             win_event: WindowEvent::Resized(_, _) | WindowEvent::SizeChanged(_, _),
@@ -96,31 +408,167 @@ fn listen(messenger: &mut Messenger, event: Event) {
     }
 }
 
-fn update(messenger: &mut Messenger, game: &mut Tetris, canvas: &WindowCanvas) -> R {
-    let mut stop = false;
+// false once the player quits
+fn update(messenger: &mut Messenger, game: &mut Tetris, canvas: &WindowCanvas) -> R<bool> {
+    // in the order they came in, for the letters of a name
+    for cmd in std::mem::take(&mut messenger.commands) {
+        // keys type the name instead while it's asked for
+        if is_naming(game) && !matches!(cmd, Command::Quit | Command::Resize) {
+            type_name(game, cmd)?;
+            continue;
+        }
 
-    while let Some(cmd) = messenger.commands.pop() {
         match cmd {
             // exit the game
-            Command::Quit => std::process::exit(0),
+            Command::Quit => {
+                if let Err(err) = submit_name(game) {
+                    eprintln!("{err}");
+                }
+
+                save_game(game);
+                return Ok(false);
+            },
             // update scale ui of the game
             Command::Resize => game.update_scale(canvas)?,
             // go left or right
-            Command::MoveMino(d) => game.request_turn(d, &mut stop),
+            Command::MoveMino(d) => game.input(Input::Turn(d)),
             // rotate clockwise or counterclockwise
-            Command::Rotate(r) => game.request_rotate(r, &mut stop),
+            Command::Rotate(r) => game.input(Input::Rotate(r)),
+            // swap with the held shape
+            Command::Hold => game.input(Input::Hold),
+            // replay controls
+            Command::Pause => {
+                if let Some(playback) = game.playback.as_mut() {
+                    playback.paused = !playback.paused;
+                }
+            },
+            Command::Speed { faster } => {
+                if let Some(playback) = game.playback.as_mut() {
+                    playback.change_speed(faster);
+                }
+            },
+            Command::StepFrame => game.step_frame(),
+            // practice controls
+            Command::Undo => game.undo(),
+            Command::Redo => game.redo(),
+            Command::Scrub(x, y) => {
+                let at = game.layout.borrow().snapshot_at(game, (x, y));
+
+                if let Some(at) = at {
+                    game.rewind_to(at);
+                }
+            },
+            Command::PlayAgain => {
+                if game.is_over() && game.playback.is_none() {
+                    play_again(game, canvas)?;
+                }
+            },
+            Command::Resume => {
+                if game.offers_resume() {
+                    resume(game, canvas)?;
+                }
+            },
+            // only while naming
+            Command::Type(_) | Command::Erase | Command::Submit => (),
         }
     }
 
-    if game.last_update.elapsed().expect("Unexpected time error.") >= game.cfg.speed && !stop {
-        game.update();
+    game.press_keys(std::mem::take(&mut messenger.presses));
+
+    let was_over = game.is_over();
+    game.update();
+
+    if game.is_over() && !was_over {
+        report(game)?;
+        save_replay(game);
+
+        if let Err(err) = record(game) {
+            eprintln!("{err}");
+        }
     }
 
+    Ok(true)
+}
+
+fn report(game: &Tetris) -> R {
+    let outcome = game
+        .state
+        .outcome
+        .ok_or(Error::Engine("the game ended without an outcome".into()))?;
+    let time = game.state.elapsed.as_secs_f32();
+
+    let (mode, rules) = (game.cfg.mode.name(), game.cfg.rules.name);
+
+    println!("{mode} ({rules} rules) over ({outcome:?}) after {time:.2}s");
+    println!("    seed: {}", game.seed);
+
+    if let Some(objective) = game.cfg.mode.objective() {
+        println!("objective: {objective:?}");
+    }
+
+    if let Some(master) = &game.state.master {
+        println!("   grade: {}", master.grade_name());
+
+        for (i, time) in master.sections.iter().enumerate() {
+            println!("     {:03}: {:.2}s", i * 100, time.as_secs_f32());
+        }
+    }
+
+    if let Some(drill) = &game.state.drill {
+        println!("  streak: {:>4}", drill.streak);
+    }
+
+    println!("{}", game.state.score);
+    println!("{}", game.state.stats.display(game.state.elapsed));
+
+    if game.playback.is_some() {
+        return Ok(());
+    }
+
+    // the seed goes last, replacing any given before
+    let mut args: Vec<&str> = Vec::new();
+    let mut given = game.cfg.args.iter();
+
+    while let Some(arg) = given.next() {
+        match arg.as_str() {
+            "--seed" => {
+                given.next();
+            },
+            arg => args.push(arg),
+        }
+    }
+
+    let seed = game.seed.to_string();
+    args.extend(["--seed", &seed]);
+
+    println!("r plays this seed again, or `tetris {}`", args.join(" "));
+
     Ok(())
 }
 
-fn render(canvas: &mut WindowCanvas, game: &Tetris) -> R {
-    game.layout.borrow().draw(game, canvas)
+fn render(canvas: &mut WindowCanvas, game: &Tetris, text: &mut Text<'_>) -> R {
+    game.layout.borrow().draw(game, canvas, text)
+}
+
+// an error is printed and put in the title once, until things are fine again
+fn show_error(canvas: &mut WindowCanvas, shown: &mut Option<String>, err: Option<Error>) {
+    let message = err.map(|e| e.to_string());
+
+    if message == *shown {
+        return;
+    }
+
+    let title = match &message {
+        Some(message) => {
+            eprintln!("{message}");
+            format!("tetris: {message}")
+        },
+        None => "tetris".to_string(),
+    };
+
+    // the title is only a hint, the error is already in the terminal
+    let _ = canvas.window_mut().set_title(&title);
+    *shown = message;
 }
 
 fn press_key(m: &mut Messenger, keycode: &Keycode) {
@@ -128,36 +576,76 @@ fn press_key(m: &mut Messenger, keycode: &Keycode) {
         return;
     }
 
-    match keycode {
-        Keycode::Left => {
-            m.commands.push(Command::MoveMino(MinoDirection::Left));
-        },
-        Keycode::Right => {
-            m.commands.push(Command::MoveMino(MinoDirection::Right));
-        },
-        Keycode::Q => {
-            m.commands.push(Command::Rotate(Rotation::CounterClockwise));
-        },
-        Keycode::E => {
-            m.commands.push(Command::Rotate(Rotation::Clockwise));
-        },
-        _ => (),
+    let command = match keycode {
+        Keycode::Left => Command::MoveMino(MinoDirection::Left),
+        Keycode::Right => Command::MoveMino(MinoDirection::Right),
+        Keycode::Q => Command::Rotate(Rotation::CounterClockwise),
+        Keycode::E => Command::Rotate(Rotation::Clockwise),
+        Keycode::W => Command::Rotate(Rotation::Half),
+        Keycode::C | Keycode::LShift => Command::Hold,
+        Keycode::Space => Command::Pause,
+        Keycode::Up => Command::Speed { faster: true },
+        Keycode::Down => Command::Speed { faster: false },
+        Keycode::Period => Command::StepFrame,
+        Keycode::Z => Command::Undo,
+        Keycode::Y => Command::Redo,
+        Keycode::R => Command::PlayAgain,
+        Keycode::L => Command::Resume,
+        Keycode::Backspace => Command::Erase,
+        Keycode::Return | Keycode::KpEnter => Command::Submit,
+        _ => return,
+    };
+
+    if matches!(command, Command::MoveMino(_) | Command::Rotate(_) | Command::Hold) {
+        m.presses += 1;
     }
+
+    m.commands.push(command);
 }
 
-fn hold_key(commands: &mut Vec<Command>, keycode: &Keycode, timestamp: &mut SystemTime) {
+// for initial rotation and hold
+fn held_keys(m: &Messenger) -> Held {
+    let rotations = [
+        (Keycode::E, Rotation::Clockwise),
+        (Keycode::Q, Rotation::CounterClockwise),
+        (Keycode::W, Rotation::Half),
+    ];
+
+    Held {
+        rotation: rotations
+            .into_iter()
+            .find(|(key, _)| m.onhold.contains_key(key))
+            .map(|(_, rot)| rot),
+        hold: [Keycode::C, Keycode::LShift]
+            .iter()
+            .any(|key| m.onhold.contains_key(key)),
+    }
+}
+
+fn hold_key(
+    commands: &mut Vec<Command>,
+    keycode: &Keycode,
+    timestamp: &mut Instant,
+    (delay, repeat): (Duration, Duration),
+) {
+    // backdate the timestamp so the next repeat comes after `repeat` rather than `delay`
+    let repeated = || {
+        let now = Instant::now();
+        now.checked_sub(delay.saturating_sub(repeat)).unwrap_or(now)
+    };
+
     // if you hold a key(e.g Escape key) more than the given milliseconds
     match keycode {
         Keycode::Escape if has_elapsed(timestamp, 500) => {
             commands.push(Command::Quit);
         },
-        Keycode::Left if has_elapsed(timestamp, 200) => {
+        Keycode::Left if has_elapsed(timestamp, delay.as_millis() as u64) => {
             commands.push(Command::MoveMino(MinoDirection::Left));
-            *timestamp = SystemTime::now();
+            *timestamp = repeated();
         },
-        Keycode::Right if has_elapsed(timestamp, 200) => {
+        Keycode::Right if has_elapsed(timestamp, delay.as_millis() as u64) => {
             commands.push(Command::MoveMino(MinoDirection::Right));
-            *timestamp = SystemTime::now();
+            *timestamp = repeated();
         },
         _ => (),
     }
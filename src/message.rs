@@ -2,16 +2,18 @@ extern crate sdl2;
 use sdl2::keyboard::Keycode;
 
 use std::collections::HashMap;
-use std::time::SystemTime;
+use std::time::Instant;
 use std::time::Duration;
 
-pub fn has_elapsed(timestamp: &SystemTime, ms: u64) -> bool {
-    timestamp.elapsed().expect("Unexpected time error.") > Duration::from_millis(ms)
+pub fn has_elapsed(timestamp: &Instant, ms: u64) -> bool {
+    timestamp.elapsed() > Duration::from_millis(ms)
 }
 
 pub struct Messenger {
     pub commands: Vec<Command>,
-    pub onhold: HashMap<Keycode, SystemTime>,
+    pub onhold: HashMap<Keycode, Instant>,
+    /// game keys pressed since the last update, not counting repeats
+    pub presses: u32,
 }
 
 impl Default for Messenger {
@@ -19,13 +21,14 @@ impl Default for Messenger {
         Self {
             commands: [].into(),
             onhold: [].into(),
+            presses: 0,
         }
     }
 }
 
 impl Messenger {
     pub fn key_hold(&mut self, keycode: Keycode) {
-        self.onhold.entry(keycode).or_insert_with(SystemTime::now);
+        self.onhold.entry(keycode).or_insert_with(Instant::now);
     }
 
     pub fn key_release(&mut self, keycode: Keycode) {
@@ -38,15 +41,50 @@ pub enum Command {
     Resize,
     MoveMino(MinoDirection),
     Rotate(Rotation),
+    Hold,
+    /// pause or resume a replay
+    Pause,
+    /// play a replay faster or slower
+    Speed {
+        faster: bool,
+    },
+    /// one frame of a paused replay
+    StepFrame,
+    /// take back the last placement in practice
+    Undo,
+    /// place the shape taken back last again
+    Redo,
+    /// jump to the practice snapshot under this point of the timeline
+    Scrub(i32, i32),
+    /// start over with the seed and settings of the game that ended
+    PlayAgain,
+    /// pick up the game quit halfway instead
+    Resume,
+    /// a letter of the name for the leaderboard
+    Type(char),
+    /// take back the last letter of the name
+    Erase,
+    /// keep the result under the name typed
+    Submit,
 }
 
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum MinoDirection {
     Left,
     Right,
 }
 
-#[derive(PartialEq, Eq, Clone, Copy)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum Rotation {
     Clockwise,
     CounterClockwise,
+    /// 180 degrees
+    Half,
+}
+
+/// rotate and hold keys held down, applied to shapes as they spawn
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub struct Held {
+    pub rotation: Option<Rotation>,
+    pub hold: bool,
 }
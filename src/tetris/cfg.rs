@@ -1,15 +1,341 @@
 use std::time::Duration;
 
+use crate::error::Error;
+
+use super::mode::Mode;
+use super::puzzle::Puzzle;
+use super::pieces::DEFAULT_HALF;
+use super::pieces::PieceSet;
+use super::rules::Timings;
+use super::rules::SpinRule;
+use super::rules::Rules;
+use super::rotation::RotationKind;
+use super::master;
+
+/// how long locked minos stay visible, works with any mode
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Stack {
+    Visible,
+    /// fade out over the given time after locking
+    Fading(Duration),
+    /// disappear as soon as they lock
+    Invisible,
+}
+
+/// how often the window is drawn, the game itself always runs at the rate of its rules
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Refresh {
+    /// once per refresh of the screen
+    Vsync,
+    /// at most this many times a second
+    Cap(u32),
+    /// as often as possible
+    Uncapped,
+}
+
 pub struct Settings {
     pub window_size: (u32, u32),
-    pub speed: Duration,
+    pub refresh: Refresh,
+    pub rules: Rules,
+    pub pieces: PieceSet,
+    /// style of the 180 degree kicks, out of the ones the piece set has
+    pub half_kicks: String,
+    /// frames of the line clear animation, instead of the one from the rules
+    pub line_clear: Option<u32>,
+    pub stack: Stack,
+    /// draw the edges of the stack, even where its minos can't be seen
+    pub outline: bool,
+    /// every mino covers 2x2 cells, making the board 5x10
+    pub big: bool,
+    pub mode: Mode,
+    /// a ttf file to write text with, the bundled font if there's none
+    pub font: Option<String>,
+    /// the player, for the leaderboard
+    pub name: Option<String>,
+    /// deals the same shapes and garbage every game, a random one if there's none
+    pub seed: Option<u64>,
+    /// the arguments these settings came from, to play the same game back
+    pub args: Vec<String>,
 }
 
 impl Default for Settings {
     fn default() -> Self {
         Self {
             window_size: (500, 700),
-            speed: Duration::from_millis(200),
+            refresh: Refresh::Vsync,
+            rules: Rules::default(),
+            pieces: PieceSet::default(),
+            half_kicks: DEFAULT_HALF.to_string(),
+            line_clear: None,
+            stack: Stack::Visible,
+            outline: false,
+            big: false,
+            mode: Mode::Marathon,
+            font: None,
+            name: None,
+            seed: None,
+            args: Vec::new(),
+        }
+    }
+}
+
+impl Settings {
+    /// side of the square of cells a mino covers, 2 in big mode
+    pub fn scale(&self) -> usize {
+        match self.big {
+            true => 2,
+            false => 1,
         }
     }
+
+    /// columns and rows of the board in minos, 5x10 in big mode
+    pub fn size(&self) -> (i32, i32) {
+        let scale = self.scale() as i32;
+        (10 / scale, 20 / scale)
+    }
+
+    pub fn timings(&self, level: u32) -> Timings {
+        let mut timings = self.rules.timings(level);
+
+        if let Some(frames) = self.line_clear {
+            timings.line_clear = frames;
+        }
+
+        timings
+    }
+
+    // e.g `tetris --mode ultra --minutes 3`, `tetris --mode dig --lines 18 --messiness 0.3`,
+    // `tetris --mode sprint` for 40 lines or `--lines 20` for another number
+    // `tetris --mode puzzle --puzzle puzzles/tsd.txt`, `tetris --mode drill`,
+    // `tetris --mode practice` or `tetris --mode master`, any of them but master with
+    // `--rules nes --level 18`, and `--clear-frames 30`.
+    // `--fade 5` or `--invisible` hide the stack, `--outline` keeps its edges drawn
+    // and `--big` doubles the size of every mino. `--pieces pentominoes` plays another
+    // built in piece set, or a piece set file, and `--rotation nrs` another rotation system
+    // out of `srs`, `ars`, `nrs` and `none`. `--half-kicks nullpomino` kicks 180 degree turns
+    // like nullpomino instead of tetr.io. `--irs off` and `--ihs off` turn off initial
+    // rotation and hold, or `on` turns them on. `--spins` is one of `none`, `t`, `mini` for minis
+    // of every shape besides full t-spins, and `all` for full spins of every shape.
+    // `--name alex` puts records on the leaderboard without asking for a name, and
+    // `--seed 42` deals the same shapes as every other game with that seed.
+    // `--refresh 144` draws at most 144 times a second, or `vsync` or `uncapped`, and
+    // `--font /usr/share/fonts/truetype/dejavu/DejaVuSans.ttf` writes text with another font
+    pub fn from_args(args: impl Iterator<Item = String>) -> Result<Self, Error> {
+        let mut settings = Self {
+            args: args.collect(),
+            ..Self::default()
+        };
+        let mut args = settings.args.clone().into_iter();
+        let mut mode = None;
+        let mut minutes = 2;
+        let mut lines = None;
+        let mut messiness = 0.0;
+        let mut puzzle = None;
+        let mut level = None;
+        let mut pieces = None;
+        let mut rotation = None;
+        let mut spins = None;
+        let mut irs = None;
+        let mut ihs = None;
+
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .ok_or(Error::Config(format!("Missing value for `{arg}`.")))
+            };
+
+            match arg.as_str() {
+                "--mode" => mode = Some(value()?),
+                "--minutes" => {
+                    minutes = value()?
+                        .parse()
+                        .map_err(|e| Error::Config(format!("Invalid minutes: {e}")))?
+                },
+                "--puzzle" => puzzle = Some(value()?),
+                "--rules" => settings.rules = Rules::from_name(&value()?)?,
+                "--pieces" => pieces = Some(value()?),
+                "--rotation" => rotation = Some(RotationKind::from_name(&value()?)?),
+                "--half-kicks" => settings.half_kicks = value()?,
+                "--spins" => spins = Some(SpinRule::from_name(&value()?)?),
+                "--irs" => irs = Some(switch(&value()?)?),
+                "--ihs" => ihs = Some(switch(&value()?)?),
+                "--invisible" => settings.stack = Stack::Invisible,
+                "--outline" => settings.outline = true,
+                "--big" => settings.big = true,
+                "--name" => settings.name = Some(value()?),
+                "--seed" => {
+                    settings.seed = Some(
+                        value()?
+                            .parse()
+                            .map_err(|e| Error::Config(format!("Invalid seed: {e}")))?,
+                    )
+                },
+                "--fade" => {
+                    let seconds = value()?
+                        .parse()
+                        .map_err(|e| Error::Config(format!("Invalid fade seconds: {e}")))?;
+                    let fade = Duration::try_from_secs_f32(seconds)
+                        .map_err(|e| Error::Config(format!("Invalid fade seconds: {e}")))?;
+
+                    settings.stack = Stack::Fading(fade);
+                },
+                "--clear-frames" => {
+                    settings.line_clear = Some(
+                        value()?
+                            .parse()
+                            .map_err(|e| Error::Config(format!("Invalid clear frames: {e}")))?,
+                    )
+                },
+                "--level" => {
+                    let start = value()?
+                        .parse()
+                        .map_err(|e| Error::Config(format!("Invalid level: {e}")))?;
+
+                    if start > master::MAX_LEVEL {
+                        return Err(Error::Config(format!(
+                            "The level goes from 0 to {}, not {start}.",
+                            master::MAX_LEVEL
+                        )));
+                    }
+
+                    level = Some(start);
+                },
+                "--lines" => {
+                    lines = Some(
+                        value()?
+                            .parse()
+                            .map_err(|e| Error::Config(format!("Invalid lines: {e}")))?,
+                    )
+                },
+                "--font" => settings.font = Some(value()?),
+                "--refresh" => settings.refresh = refresh(&value()?)?,
+                "--messiness" => {
+                    messiness = value()?
+                        .parse()
+                        .map_err(|e| Error::Config(format!("Invalid messiness: {e}")))?;
+
+                    // `nan` and `inf` parse too
+                    if !(0.0..=1.0).contains(&messiness) {
+                        return Err(Error::Config(format!(
+                            "The messiness goes from 0 to 1, not {messiness}."
+                        )));
+                    }
+                },
+                _ => return Err(Error::Config(format!("Unknown argument `{arg}`."))),
+            }
+        }
+
+        if let Some(level) = level {
+            settings.rules.start_level = level;
+        }
+
+        if let Some(rotation) = rotation {
+            settings.rules.rotation = rotation;
+        }
+
+        if let Some(spins) = spins {
+            settings.rules.spins = spins;
+        }
+
+        if let Some(irs) = irs {
+            settings.rules.irs = irs;
+        }
+
+        if let Some(ihs) = ihs {
+            settings.rules.ihs = ihs;
+        }
+
+        settings.mode = match mode.as_deref() {
+            None | Some("marathon") => Mode::Marathon,
+            Some("ultra") => Mode::ultra(minutes)?,
+            Some("dig") => Mode::Dig {
+                lines: lines.unwrap_or(10).max(1),
+                messiness,
+            },
+            Some("sprint") => Mode::Sprint {
+                lines: lines.unwrap_or(40).max(1),
+            },
+            Some("drill") => Mode::Drill,
+            Some("practice") => Mode::Practice,
+            // master is always played with tgm rules
+            Some("master") => {
+                settings.rules = Rules::tgm();
+                Mode::Master
+            },
+            Some("puzzle") => {
+                let path =
+                    puzzle.ok_or(Error::Config("Puzzle mode needs a `--puzzle` file.".into()))?;
+                Mode::Puzzle(Box::new(Puzzle::load(path)?))
+            },
+            Some(other) => return Err(Error::Config(format!("Unknown mode `{other}`."))),
+        };
+
+        settings.pieces = PieceSet::load(
+            pieces
+                .as_deref()
+                .unwrap_or(settings.rules.rotation.pieces()),
+            &settings.half_kicks,
+        )?;
+
+        // spawning and the drill take every piece to fit, and big minos make the board smaller
+        let (columns, rows) = settings.size();
+
+        for piece in settings.pieces.pieces.iter() {
+            let fits = piece.layouts.iter().all(|layout| {
+                let extent = |axis: usize| {
+                    let values = layout.iter().map(|cell| cell[axis]);
+                    values.clone().max().unwrap_or(0) - values.min().unwrap_or(0) + 1
+                };
+
+                extent(0) <= columns && extent(1) <= rows
+            });
+
+            if !fits
+                || piece
+                    .cells(0, piece.spawn)
+                    .any(|[_, r]| !(0..rows).contains(&r))
+            {
+                return Err(Error::Config(format!(
+                    "`{}` of the {} set doesn't fit on the {columns}x{rows} board.",
+                    piece.name, settings.pieces.name
+                )));
+            }
+        }
+
+        if let Mode::Puzzle(puzzle) = &settings.mode {
+            let set = &settings.pieces;
+
+            if let Some(c) = puzzle.pieces.iter().find(|&&c| set.find(c).is_none()) {
+                return Err(Error::Config(format!(
+                    "The puzzle uses `{c}`, which isn't in the {} set.",
+                    set.name
+                )));
+            }
+        }
+
+        Ok(settings)
+    }
+}
+
+// `vsync`, `uncapped` or a number of frames a second, e.g `60`, `120` or `144`
+fn refresh(value: &str) -> Result<Refresh, Error> {
+    match value {
+        "vsync" => Ok(Refresh::Vsync),
+        "uncapped" => Ok(Refresh::Uncapped),
+        hz => match hz.parse() {
+            Ok(0) | Err(_) => Err(Error::Config(format!(
+                "Expected `vsync`, `uncapped` or frames a second, not `{value}`."
+            ))),
+            Ok(hz) => Ok(Refresh::Cap(hz)),
+        },
+    }
+}
+
+// `on` or `off`
+fn switch(value: &str) -> Result<bool, Error> {
+    match value {
+        "on" => Ok(true),
+        "off" => Ok(false),
+        _ => Err(Error::Config(format!("Expected `on` or `off`, not `{value}`."))),
+    }
 }
@@ -0,0 +1,100 @@
+use std::collections::VecDeque;
+use std::collections::HashSet;
+
+use crate::message;
+use message::Rotation;
+
+use super::rotation::RotationSystem;
+use super::pieces::Piece;
+
+// finesse is placing a shape with as few key presses as possible. holding a
+// direction until the shape hits the wall is a single press, and only moves
+// and turns count, as the shape falls on its own. placements that need the
+// stack to get into, like tucks and spins, are never judged.
+
+/// a placement the drill asks for
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Target {
+    pub orientation: usize,
+    /// top left of the layout box, with the shape resting on the floor
+    pub origin: [i32; 2],
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct Drill {
+    /// where the focused shape has to go, picked when it spawns
+    pub target: Option<Target>,
+    /// shapes placed right in a row
+    pub streak: u32,
+}
+
+// the columns of each cell and the rows relative to the top one, in order
+fn footprint(cells: impl Iterator<Item = [i32; 2]>) -> Vec<[i32; 2]> {
+    let mut cells: Vec<[i32; 2]> = cells.collect();
+    let top = cells.iter().map(|&[_, r]| r).min().unwrap_or(0);
+
+    for cell in cells.iter_mut() {
+        cell[1] -= top;
+    }
+
+    cells.sort();
+    cells
+}
+
+/// fewest key presses that take a shape from `spawn` to the columns and orientation of
+/// `placed`, `None` when moving and turning on an empty board can't get it there
+pub fn min_keys(
+    piece: &Piece,
+    system: &dyn RotationSystem,
+    spawn: [i32; 2],
+    columns: i32,
+    placed: &[[i32; 2]],
+) -> Option<u32> {
+    let goal = footprint(placed.iter().copied());
+
+    let is_free = |[c, _]: [i32; 2]| (0..columns).contains(&c);
+    let fits = |orientation: usize, origin: [i32; 2]| piece.cells(orientation, origin).all(is_free);
+
+    let rotations = [Rotation::Clockwise, Rotation::CounterClockwise, Rotation::Half];
+
+    let mut seen = HashSet::from([(spawn, 0)]);
+    let mut queue = VecDeque::from([(spawn, 0, 0)]);
+
+    while let Some((origin, orientation, keys)) = queue.pop_front() {
+        if footprint(piece.cells(orientation, origin)) == goal {
+            return Some(keys);
+        }
+
+        let [x, y] = origin;
+        let mut next = Vec::new();
+
+        for dc in [-1, 1] {
+            // a tap, then holding the direction until the wall
+            if fits(orientation, [x + dc, y]) {
+                next.push(([x + dc, y], orientation));
+            }
+
+            let mut far = x;
+
+            while fits(orientation, [far + dc, y]) {
+                far += dc;
+            }
+
+            next.push(([far, y], orientation));
+        }
+
+        for rot in rotations {
+            if let Some(turned) = system.rotate(piece, orientation, origin, rot, &is_free) {
+                next.push(turned);
+            }
+        }
+
+        for state in next {
+            if seen.insert(state) {
+                queue.push_back((state.0, state.1, keys + 1));
+            }
+        }
+    }
+
+    None
+}
@@ -0,0 +1,39 @@
+use super::rng::Xoshiro256;
+
+/// garbage rows kept on the board at once, the rest rise as you dig
+pub const VISIBLE_ROWS: u32 = 10;
+
+pub const COLOR: [u8; 3] = [128, 128, 128];
+
+#[derive(Debug, Clone)]
+pub struct Garbage {
+    /// garbage rows that are yet to be cleared, including the ones on the board
+    pub left: u32,
+    /// 0.0 keeps the hole in the same column, 1.0 moves it on every row
+    pub messiness: f32,
+    /// column of the hole in the last row that rose
+    pub last_hole: Option<usize>,
+}
+
+impl Garbage {
+    pub fn new(lines: u32, messiness: f32) -> Self {
+        Self {
+            left: lines,
+            messiness: messiness.clamp(0.0, 1.0),
+            last_hole: None,
+        }
+    }
+
+    pub fn next_hole(&mut self, rng: &mut Xoshiro256, columns: usize) -> usize {
+        let hole = match self.last_hole {
+            // keep the hole where it was
+            Some(last) if !rng.chance(self.messiness as f64) => last,
+            // move the hole to any other column
+            Some(last) => (last + 1 + rng.below(columns - 1)) % columns,
+            None => rng.below(columns),
+        };
+
+        self.last_hole = Some(hole);
+        hole
+    }
+}
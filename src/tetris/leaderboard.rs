@@ -0,0 +1,355 @@
+use std::collections::BTreeMap;
+use std::time::SystemTime;
+use std::cmp::Ordering;
+use std::path::Path;
+use std::fmt;
+use std::fs;
+
+use crate::error::Error;
+
+use super::mode::Outcome;
+use super::mode::Mode;
+use super::cfg::Settings;
+use super::pieces::DEFAULT_HALF;
+use super::rules::Rules;
+use super::Tetris;
+
+// the leaderboard file has a line for every result kept, e.g
+//
+//   ultra-2m standard 2026-10-19 alex 12400 38 104 120000
+//
+// that's the board, the rules, the date, the player, then the points, lines,
+// pieces and milliseconds played. names can't have spaces in them. a board is
+// the mode with whatever changes what a result is worth, see `board_name`.
+
+pub const FILE: &str = "leaderboard.txt";
+
+/// results kept for each mode and rules
+pub const KEPT: usize = 10;
+
+/// longest name typed in the window
+pub const NAME_LENGTH: usize = 16;
+
+/// what makes a result better than another
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Ranking {
+    /// more points
+    Points,
+    /// fewer pieces
+    Pieces,
+    /// less time
+    Time,
+}
+
+impl Ranking {
+    /// how the mode is ranked, `None` when it isn't
+    pub fn of(mode: &Mode) -> Option<Self> {
+        match mode {
+            Mode::Ultra(_) | Mode::Marathon => Some(Ranking::Points),
+            Mode::Dig { .. } => Some(Ranking::Pieces),
+            Mode::Sprint { .. } => Some(Ranking::Time),
+            Mode::Puzzle(_) | Mode::Master | Mode::Drill | Mode::Practice => None,
+        }
+    }
+
+    /// the better result first
+    pub fn compare(&self, a: &Entry, b: &Entry) -> Ordering {
+        match self {
+            Ranking::Points => b.points.cmp(&a.points).then(a.millis.cmp(&b.millis)),
+            Ranking::Pieces => a.pieces.cmp(&b.pieces).then(a.millis.cmp(&b.millis)),
+            Ranking::Time => a.millis.cmp(&b.millis).then(a.pieces.cmp(&b.pieces)),
+        }
+    }
+
+    /// what the result is ranked by, e.g `12400`, `31 pieces` or `52.18s`
+    pub fn value(&self, entry: &Entry) -> String {
+        match self {
+            Ranking::Points => entry.points.to_string(),
+            Ranking::Pieces => format!("{} pieces", entry.pieces),
+            Ranking::Time => format!("{:.2}s", entry.millis as f32 / 1000.0),
+        }
+    }
+}
+
+/// the mode with its parameters, the piece set when it isn't the one of the rotation
+/// system, whatever differs from the rules and whether minos are big, e.g `ultra-3m`,
+/// `dig-18-0.3`, `sprint-40-pentominoes` or `marathon-lv18-clear30`
+pub fn board_name(cfg: &Settings) -> String {
+    let mut name = match &cfg.mode {
+        Mode::Ultra(limit) => format!("ultra-{}m", limit.as_secs() / 60),
+        Mode::Dig { lines, messiness } => format!("dig-{lines}-{messiness}"),
+        Mode::Sprint { lines } => format!("sprint-{lines}"),
+        mode => mode.name().to_string(),
+    };
+
+    if cfg.pieces.name != cfg.rules.rotation.pieces() {
+        name += "-";
+        name += &cfg.pieces.name.replace(char::is_whitespace, "_");
+    }
+
+    // points and speed go with the level, and the rest changes what can be done in time
+    let (rules, given) = (Rules::from_name(cfg.rules.name).unwrap_or_default(), &cfg.rules);
+
+    if given.start_level != rules.start_level {
+        name += &format!("-lv{}", given.start_level);
+    }
+
+    if given.rotation != rules.rotation {
+        name += &format!("-{}", given.rotation.name());
+    }
+
+    if cfg.half_kicks != DEFAULT_HALF {
+        name += &format!("-half_{}", cfg.half_kicks);
+    }
+
+    if given.spins != rules.spins {
+        name += &format!("-spins_{}", given.spins.name());
+    }
+
+    for (kind, on, default) in [("irs", given.irs, rules.irs), ("ihs", given.ihs, rules.ihs)] {
+        if on != default {
+            let switch = match on {
+                true => "on",
+                false => "off",
+            };
+
+            name += &format!("-{kind}_{switch}");
+        }
+    }
+
+    if let Some(frames) = cfg.line_clear {
+        name += &format!("-clear{frames}");
+    }
+
+    if cfg.big {
+        name += "-big";
+    }
+
+    name
+}
+
+/// the board of a game that's over, shown in the window
+#[derive(Debug, Clone)]
+pub struct Standing {
+    /// with the result of the game in place when it made it
+    pub board: Vec<Entry>,
+    /// where the result is on the board, `None` if it didn't make it
+    pub rank: Option<usize>,
+    /// the name typed so far, while the player is asked for one
+    pub naming: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    /// e.g `2026-10-19`
+    pub date: String,
+    pub name: String,
+    pub points: u32,
+    pub lines: u32,
+    pub pieces: u32,
+    pub millis: u64,
+}
+
+impl Entry {
+    /// the result of a game that's over, `None` if it doesn't count, e.g dig or sprint
+    /// left unfinished
+    pub fn of(game: &Tetris, name: &str) -> Option<Self> {
+        Ranking::of(&game.cfg.mode)?;
+
+        let finished = match game.cfg.mode {
+            Mode::Dig { .. } => game.state.outcome == Some(Outcome::Cleared),
+            Mode::Sprint { .. } => game.state.outcome == Some(Outcome::Finished),
+            _ => game.is_over(),
+        };
+
+        if !finished || game.playback.is_some() {
+            return None;
+        }
+
+        Some(Self {
+            date: today(),
+            name: name.to_string(),
+            points: game.state.score.points,
+            lines: game.state.score.lines,
+            pieces: game.state.stats.pieces,
+            millis: game.state.elapsed.as_millis() as u64,
+        })
+    }
+}
+
+impl fmt::Display for Entry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:<12} {:>8} pts {:>4} lines {:>4} pieces {:>8.2}s  {}",
+            self.name,
+            self.points,
+            self.lines,
+            self.pieces,
+            self.millis as f32 / 1000.0,
+            self.date
+        )
+    }
+}
+
+// the date in utc, from the days since 1970 without a calendar crate
+fn today() -> String {
+    let secs = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+
+    // howard hinnant's days from civil, the other way around
+    let days = (secs / 86400) as i64 + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = match month_index < 10 {
+        true => month_index + 3,
+        false => month_index - 9,
+    };
+    let year = year_of_era + era * 400 + (month <= 2) as i64;
+
+    format!("{year:04}-{month:02}-{day:02}")
+}
+
+/// names go in the file between spaces
+pub fn clean_name(name: &str) -> String {
+    let name: String = name
+        .trim()
+        .chars()
+        .map(|c| match c.is_whitespace() {
+            true => '_',
+            false => c,
+        })
+        .collect();
+
+    match name.is_empty() {
+        true => "player".into(),
+        false => name,
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct Leaderboard {
+    /// results by mode and rules, the best first
+    pub boards: BTreeMap<(String, String), Vec<Entry>>,
+}
+
+impl Leaderboard {
+    /// the leaderboard file, or an empty leaderboard when there's none yet
+    pub fn load() -> Result<Self, Error> {
+        if !Path::new(FILE).exists() {
+            return Ok(Self::default());
+        }
+
+        let text = fs::read_to_string(FILE).map_err(Error::io(format!("Couldn't read {FILE}")))?;
+        Self::parse(&text).map_err(|e| Error::Config(format!("Invalid {FILE}: {e}")))
+    }
+
+    pub fn parse(text: &str) -> Result<Self, Error> {
+        let mut leaderboard = Self::default();
+
+        for line in text.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let words: Vec<&str> = line.split_whitespace().collect();
+
+            let [mode, rules, date, name, points, lines, pieces, millis] = words[..] else {
+                return Err(Error::Config(format!("`{line}` should have 8 values.")));
+            };
+
+            let invalid = |e| Error::Config(format!("Invalid number in `{line}`: {e}"));
+
+            let entry = Entry {
+                date: date.to_string(),
+                name: name.to_string(),
+                points: points.parse().map_err(invalid)?,
+                lines: lines.parse().map_err(invalid)?,
+                pieces: pieces.parse().map_err(invalid)?,
+                millis: millis.parse().map_err(invalid)?,
+            };
+
+            leaderboard
+                .boards
+                .entry((mode.to_string(), rules.to_string()))
+                .or_default()
+                .push(entry);
+        }
+
+        Ok(leaderboard)
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+
+        for ((mode, rules), entries) in self.boards.iter() {
+            for e in entries.iter() {
+                text += &format!(
+                    "{mode} {rules} {} {} {} {} {} {}\n",
+                    e.date, e.name, e.points, e.lines, e.pieces, e.millis
+                );
+            }
+        }
+
+        text
+    }
+
+    pub fn save(&self) -> Result<(), Error> {
+        fs::write(FILE, self.to_text()).map_err(Error::io(format!("Couldn't save {FILE}")))
+    }
+
+    /// the results of a board, see `board_name`
+    pub fn board(&self, board: &str, rules: &str) -> &[Entry] {
+        self.boards
+            .get(&(board.to_string(), rules.to_string()))
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    /// where the result would place, `None` if it wouldn't make the board
+    pub fn rank(&self, game: &Tetris, entry: &Entry) -> Option<usize> {
+        let ranking = Ranking::of(&game.cfg.mode)?;
+        let board = self.board(&board_name(&game.cfg), game.cfg.rules.name);
+        let rank = board
+            .iter()
+            .take_while(|e| ranking.compare(e, entry) != Ordering::Greater)
+            .count();
+
+        (rank < KEPT).then_some(rank)
+    }
+
+    /// add the result of a game to its board, keeping the best ones in order
+    pub fn insert(&mut self, game: &Tetris, entry: Entry) {
+        let Some(ranking) = Ranking::of(&game.cfg.mode) else {
+            return;
+        };
+
+        let key = (board_name(&game.cfg), game.cfg.rules.name.to_string());
+        let board = self.boards.entry(key).or_default();
+
+        board.push(entry);
+        board.sort_by(|a, b| ranking.compare(a, b));
+        board.truncate(KEPT);
+    }
+}
+
+impl fmt::Display for Leaderboard {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for ((mode, rules), entries) in self.boards.iter() {
+            writeln!(f, "{mode} ({rules} rules)")?;
+
+            for (i, entry) in entries.iter().enumerate() {
+                writeln!(f, "{:>3}. {entry}", i + 1)?;
+            }
+        }
+
+        Ok(())
+    }
+}
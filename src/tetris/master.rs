@@ -0,0 +1,162 @@
+use std::time::Duration;
+
+use super::rules::Timings;
+
+/// internal gravity in 1/256 rows per frame, from the level each step starts at
+const GRAVITY: [(u32, u32); 30] = [
+    (0, 4),
+    (30, 6),
+    (35, 8),
+    (40, 10),
+    (50, 12),
+    (60, 16),
+    (70, 32),
+    (80, 48),
+    (90, 64),
+    (100, 80),
+    (120, 96),
+    (140, 112),
+    (160, 128),
+    (170, 144),
+    (200, 4),
+    (220, 32),
+    (230, 64),
+    (233, 96),
+    (236, 128),
+    (239, 160),
+    (243, 192),
+    (247, 224),
+    (251, 256),
+    (300, 512),
+    (330, 768),
+    (360, 1024),
+    (400, 1280),
+    (420, 1024),
+    (450, 768),
+    (500, 5120),
+];
+
+/// delays of each section, they get shorter past level 500
+pub const TIMINGS: [(u32, Timings); 6] = [
+    (0, timings(25, 25, 40, 14, 30)),
+    (500, timings(25, 25, 25, 8, 30)),
+    (600, timings(25, 16, 16, 8, 30)),
+    (700, timings(16, 12, 12, 8, 30)),
+    (800, timings(12, 6, 6, 8, 30)),
+    (900, timings(12, 6, 6, 6, 17)),
+];
+
+/// score needed for each grade, from 9 up to S9
+pub const GRADES: [(u32, &str); 18] = [
+    (0, "9"),
+    (400, "8"),
+    (800, "7"),
+    (1400, "6"),
+    (2000, "5"),
+    (3500, "4"),
+    (5500, "3"),
+    (8000, "2"),
+    (12000, "1"),
+    (16000, "S1"),
+    (22000, "S2"),
+    (30000, "S3"),
+    (40000, "S4"),
+    (52000, "S5"),
+    (66000, "S6"),
+    (82000, "S7"),
+    (100000, "S8"),
+    (120000, "S9"),
+];
+
+/// level, index into `GRADES` and seconds to beat for the grand master grade, grade 1 by
+/// level 300, S4 by 500 and S9 by 999
+const GM_CHECKS: [(u32, usize, u64); 3] = [(300, 8, 255), (500, 12, 450), (999, 17, 810)];
+
+pub const MAX_LEVEL: u32 = 999;
+
+const fn timings(are: u32, line_are: u32, line_clear: u32, das: u32, lock: u32) -> Timings {
+    Timings {
+        are,
+        line_are,
+        line_clear,
+        das,
+        lock: Some(lock),
+    }
+}
+
+pub fn gravity(level: u32) -> u32 {
+    GRAVITY
+        .iter()
+        .rev()
+        .find(|&&(from, _)| level >= from)
+        .map(|&(_, gravity)| gravity)
+        .unwrap_or(GRAVITY[0].1)
+}
+
+/// levels stop at the end of each section until a line is cleared
+pub fn is_section_stop(level: u32) -> bool {
+    level % 100 == 99 || level == MAX_LEVEL - 1
+}
+
+/// grade and section times of a master game
+#[derive(Debug, Clone)]
+pub struct Master {
+    pub grade: usize,
+    /// time it took to clear each section of 100 levels
+    pub sections: Vec<Duration>,
+    /// still on track for the grand master grade
+    pub gm_eligible: bool,
+    /// grand master checkpoints reached, of `GM_CHECKS`
+    pub checks_passed: usize,
+}
+
+impl Default for Master {
+    fn default() -> Self {
+        Self {
+            grade: 0,
+            sections: Vec::new(),
+            gm_eligible: true,
+            checks_passed: 0,
+        }
+    }
+}
+
+impl Master {
+    pub fn grade_name(&self) -> &'static str {
+        match self.checks_passed == GM_CHECKS.len() && self.gm_eligible {
+            true => "GM",
+            false => GRADES[self.grade].1,
+        }
+    }
+
+    pub fn update(&mut self, level: u32, points: u32, elapsed: Duration) {
+        self.grade = GRADES
+            .iter()
+            .rposition(|&(needed, _)| points >= needed)
+            .unwrap_or(0);
+
+        // record the time a section was finished at
+        let section = match level >= MAX_LEVEL {
+            true => 10,
+            false => (level / 100) as usize,
+        };
+
+        while self.sections.len() < section {
+            let finished = self.sections.iter().sum::<Duration>();
+            self.sections.push(elapsed.saturating_sub(finished));
+        }
+
+        // grand master checkpoints
+        while let Some(&(at, grade, seconds)) = GM_CHECKS.get(self.checks_passed) {
+            if level < at {
+                break;
+            }
+
+            if self.grade < grade || elapsed > Duration::from_secs(seconds) {
+                self.gm_eligible = false;
+            }
+
+            self.checks_passed += 1;
+        }
+    }
+}
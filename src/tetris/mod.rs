@@ -2,8 +2,6 @@ extern crate sdl2;
 use sdl2::render::WindowCanvas;
 
 extern crate rand;
-use rand::rngs::ThreadRng;
-use rand::Rng;
 
 pub mod mino;
 use mino::ShapeVariant;
@@ -13,6 +11,57 @@ use mino::Mino;
 pub mod cfg;
 use cfg::Settings;
 
+pub mod mode;
+use mode::Outcome;
+use mode::Mode;
+
+pub mod garbage;
+
+pub mod puzzle;
+use puzzle::Objective;
+
+pub mod rules;
+use rules::SpinRule;
+use rules::Timings;
+use rules::Fall;
+
+pub mod randomizer;
+
+pub mod rng;
+
+pub mod master;
+
+pub mod pieces;
+use pieces::Piece;
+
+pub mod rotation;
+
+pub mod finesse;
+use finesse::Target;
+
+pub mod stats;
+
+pub mod practice;
+use practice::Practice;
+
+pub mod state;
+use state::State;
+
+pub mod leaderboard;
+use leaderboard::Standing;
+use leaderboard::Entry;
+
+pub mod replay;
+use replay::Playback;
+use replay::Replay;
+use replay::Check;
+use replay::Event;
+
+pub mod score;
+use score::LineClear;
+use score::SpinKind;
+use score::Spin;
+
 use crate::display;
 use display::TetrisDisplay;
 use display::Draw;
@@ -20,84 +69,508 @@ use display::Draw;
 use crate::message;
 use message::MinoDirection;
 use message::Rotation;
+use message::Held;
 
-use std::time::SystemTime;
+use crate::error::Error;
+
+use std::time::Instant;
+use std::path::Path;
+use std::fs;
+use std::time::Duration;
 use std::cell::RefCell;
 
 pub struct Tetris {
-    pub minos: [[Option<Mino>; 10]; 20],
+    pub state: State,
     pub layout: Box<RefCell<dyn Draw>>,
-    pub focused_shape: Option<Shape>,
-    pub last_update: SystemTime,
-    pub rng: ThreadRng,
+    /// when the last frame was simulated
+    pub last_update: Instant,
+    /// rows the shape fell in the last frame, to draw it sliding down between frames
+    pub fallen: u32,
+    pub practice: Option<Practice>,
+    /// the best result of the mode and rules on the leaderboard, to pace against
+    pub best: Option<Entry>,
+    /// the leaderboard of the mode and rules, once the game is over
+    pub standing: Option<Standing>,
+    /// a game quit halfway can be picked up in place of this one
+    pub resumable: bool,
+    /// everything the player did so far, by frame
+    pub log: Vec<(u64, Event)>,
+    /// the score after every shape locked so far
+    pub checks: Vec<Check>,
+    /// the replay played instead of taking input
+    pub playback: Option<Playback>,
+    pub seed: u64,
     pub cfg: Settings,
 }
 
+/// catching up on more frames than this at once is treated as a stall
+const MAX_CATCH_UP: u32 = 15;
+
+/// the board cells covered by the mino at `pos`, more than one when scaled up
+fn cells(scale: usize, [column, row]: [usize; 2]) -> impl Iterator<Item = [usize; 2]> {
+    (0..scale * scale).map(move |i| [column * scale + i % scale, row * scale + i / scale])
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Phase {
+    /// the focused shape falls and can be moved
+    Falling,
+    /// the focused shape has been grounded for this many frames
+    Locking(u32),
+    /// full rows stay on the board for this many more frames
+    LineClear(u32),
+    /// this many more frames until the next shape spawns
+    Entry(u32),
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Input {
+    Turn(MinoDirection),
+    Rotate(Rotation),
+    Hold,
+}
+
 impl Default for Tetris {
     fn default() -> Self {
-        let cfg = Settings::default();
+        Self::new(Settings::default())
+    }
+}
+
+impl Tetris {
+    /// with the seed of the settings, or a random one
+    pub fn new(cfg: Settings) -> Self {
+        let seed = cfg.seed.unwrap_or_else(rand::random);
+        Self::with_seed(cfg, seed)
+    }
+
+    /// the game of a replay, without taking input
+    pub fn playback(cfg: Settings, replay: Replay) -> Self {
+        let mut game = Self::with_seed(cfg, replay.seed);
+        game.playback = Some(Playback::new(replay));
+        game
+    }
+
+    /// keep the game to be resumed, its replay so far and then its state
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), Error> {
+        let path = path.as_ref();
+        let text = format!("{}state\n{}", self.replay().to_text(), self.state.to_text());
 
-        Self {
-            focused_shape: None,
-            last_update: SystemTime::now(),
+        fs::write(path, text).map_err(Error::io(format!("Couldn't save {}", path.display())))
+    }
+
+    /// the game kept by `save`, going on right where it was left
+    pub fn resume(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)
+            .map_err(Error::io(format!("Couldn't read {}", path.display())))?;
+        let invalid = |e| Error::Config(format!("Invalid save {}: {e}", path.display()));
+
+        let (replay, state) = text
+            .split_once("\nstate\n")
+            .ok_or(Error::Config(format!("{} has no state to resume.", path.display())))?;
+        let replay = Replay::parse(replay).map_err(invalid)?;
+        let cfg = Settings::from_args(replay.args.iter().cloned())?;
+        let state = State::parse(state, &cfg).map_err(invalid)?;
+
+        let mut game = Self::with_seed(cfg, replay.seed);
+        game.state = state;
+        game.log = replay.events;
+        game.checks = replay.checks;
+
+        if replay.end != Some(game.check()) {
+            return Err(Error::Config(format!(
+                "The state of {} isn't where its replay ends.",
+                path.display()
+            )));
+        }
+
+        // what happened before it was saved can't be undone anymore
+        if game.practice.is_some() {
+            game.practice = Some(Practice::new(&game));
+        }
+
+        Ok(game)
+    }
+
+    /// everything the game needs to be played back
+    pub fn replay(&self) -> Replay {
+        Replay {
+            seed: self.seed,
+            args: self.cfg.args.clone(),
+            events: self.log.clone(),
+            checks: self.checks.clone(),
+            end: Some(self.check()),
+        }
+    }
+
+    /// the score right now, for replays to be verified against
+    pub fn check(&self) -> Check {
+        Check {
+            tick: self.state.tick,
+            points: self.state.score.points,
+            lines: self.state.score.lines,
+            millis: self.state.elapsed.as_millis() as u64,
+        }
+    }
+
+    /// the same seed and settings always deal the same shapes and garbage
+    pub fn with_seed(cfg: Settings, seed: u64) -> Self {
+        let mut game = Self {
+            state: State::new(&cfg, seed),
             layout: Box::new(RefCell::new(TetrisDisplay::init(&cfg))),
-            minos: [[None; 10]; 20],
-            rng: rand::thread_rng(),
+            last_update: Instant::now(),
+            fallen: 0,
+            practice: None,
+            best: None,
+            standing: None,
+            resumable: false,
+            log: Vec::new(),
+            checks: Vec::new(),
+            playback: None,
+            seed,
             cfg,
+        };
+
+        // pre-fill the bottom of the board
+        game.rise_garbage();
+        game.fill_queue();
+
+        if let Mode::Practice = game.cfg.mode {
+            game.practice = Some(Practice::new(&game));
+        }
+
+        game
+    }
+
+    pub fn is_over(&self) -> bool {
+        self.state.outcome.is_some()
+    }
+
+    /// the game quit halfway is offered until a shape locks, and again once this one is over
+    pub fn offers_resume(&self) -> bool {
+        self.resumable && self.playback.is_none() && (self.checks.is_empty() || self.is_over())
+    }
+
+    /// nothing moves until the player does something, e.g a paused replay or a game over
+    pub fn is_idle(&self) -> bool {
+        self.is_over() || self.playback.as_ref().map(|p| p.paused).unwrap_or(false)
+    }
+
+    /// a move from the player, ignored while a replay plays
+    pub fn input(&mut self, input: Input) {
+        if self.playback.is_none() {
+            self.apply(Event::Input(input));
+        }
+    }
+
+    /// the rotate and hold keys held down right now
+    pub fn hold_keys(&mut self, held: Held) {
+        if self.playback.is_none() && held != self.state.held {
+            self.apply(Event::Held(held));
+        }
+    }
+
+    /// count keys pressed since the last update, not repeats of held ones
+    pub fn press_keys(&mut self, presses: u32) {
+        if self.playback.is_none() && presses > 0 {
+            self.apply(Event::Keys(presses));
+        }
+    }
+
+    // everything the player does goes through here, to be recorded
+    fn apply(&mut self, event: Event) {
+        if self.is_over() {
+            return;
+        }
+
+        self.log.push((self.state.tick, event));
+
+        match event {
+            Event::Input(Input::Turn(dir)) => self.request_turn(dir),
+            Event::Input(Input::Rotate(rot)) => self.request_rotate(rot),
+            Event::Input(Input::Hold) => self.request_hold(),
+            Event::Held(held) => self.state.held = held,
+            Event::Keys(presses) => {
+                self.state.stats.keys += presses;
+                self.state.piece_keys += presses;
+            },
+        }
+    }
+
+    // the events of the replay due before the next frame
+    fn play_events(&mut self) {
+        let Some(playback) = self.playback.as_mut() else {
+            return;
+        };
+
+        let mut due = Vec::new();
+
+        while let Some(&(tick, event)) = playback.events.front() {
+            if tick > self.state.tick {
+                break;
+            }
+
+            due.push(event);
+            playback.events.pop_front();
+        }
+
+        for event in due {
+            self.apply(event);
+        }
+    }
+
+    /// simulate a single frame of a paused replay
+    pub fn step_frame(&mut self) {
+        if self.playback.as_ref().map(|p| p.paused).unwrap_or(false) && !self.is_over() {
+            self.step();
+        }
+    }
+
+    pub fn remaining_time(&self) -> Option<Duration> {
+        let limit = self.cfg.mode.time_limit()?;
+        Some(limit.saturating_sub(self.state.elapsed))
+    }
+
+    fn finish(&mut self, outcome: Outcome) {
+        if self.state.outcome.is_none() {
+            self.state.outcome = Some(outcome);
         }
     }
-}
 
-impl Tetris {
     pub fn update_scale(&mut self, canvas: &WindowCanvas) -> crate::R {
-        self.cfg.window_size = canvas.output_size()?;
+        self.cfg.window_size = canvas.output_size().map_err(Error::Render)?;
         self.layout.borrow_mut().update(self);
 
         Ok(())
     }
 
+    // how long a frame lasts in real time, shorter or longer for a replay
+    fn frame(&self) -> Duration {
+        let frame = self.cfg.rules.frames(1);
+
+        match &self.playback {
+            Some(playback) => frame.div_f32(playback.speed),
+            None => frame,
+        }
+    }
+
+    /// how far the clock is into the next frame, from 0.0 to 1.0
+    pub fn frame_progress(&self) -> f32 {
+        if self.is_idle() {
+            return 1.0;
+        }
+
+        (self.last_update.elapsed().as_secs_f32() / self.frame().as_secs_f32()).min(1.0)
+    }
+
+    /// simulate the frames that passed since the last update
     pub fn update(&mut self) {
-        // reset timer
-        self.last_update = SystemTime::now();
+        let frame = self.frame();
 
-        // generate a shape if there is none
-        if self.focused_shape.is_none() {
-            self.generate();
-            return; // skip for next iteration
+        // a paused replay only moves a frame at a time
+        if self.playback.as_ref().map(|p| p.paused).unwrap_or(false) {
+            self.last_update = Instant::now();
+            return;
         }
 
-        // is locked
-        let is_locked = |column: usize, row: usize| -> bool {
-            self.minos[row][column].map(|m| m.locked).unwrap_or(false)
+        let behind = self.last_update.elapsed();
+
+        // don't try to catch up after a stall, e.g while the window is dragged
+        if behind > frame * MAX_CATCH_UP {
+            self.last_update = Instant::now()
+                .checked_sub(frame)
+                .unwrap_or_else(Instant::now);
+        }
+
+        while !self.is_over() && self.last_update.elapsed() >= frame {
+            self.last_update += frame;
+            self.step();
+        }
+    }
+
+    fn step(&mut self) {
+        self.fallen = 0;
+        self.play_events();
+
+        self.state.tick += 1;
+        self.state.elapsed += self.cfg.rules.frames(1);
+
+        // the clock runs out, freeze the game right where it is
+        if let Some(limit) = self.cfg.mode.time_limit() {
+            if self.state.elapsed >= limit {
+                self.state.elapsed = limit;
+                self.finish(Outcome::TimeUp);
+                return;
+            }
+        }
+
+        match self.state.phase {
+            Phase::Falling | Phase::Locking(_) => self.fall(),
+            Phase::LineClear(0) => {
+                self.collapse();
+                self.state.phase = Phase::Entry(self.timings().line_are);
+            },
+            Phase::LineClear(frames) => self.state.phase = Phase::LineClear(frames - 1),
+            Phase::Entry(0) => {
+                self.rise_garbage();
+
+                self.state.phase = Phase::Falling;
+
+                if !self.is_over() {
+                    self.generate();
+                    self.replay_buffered();
+                }
+            },
+            Phase::Entry(frames) => self.state.phase = Phase::Entry(frames - 1),
+        }
+    }
+
+    fn fall(&mut self) {
+        if self.state.focused_shape.is_none() {
+            return; // skip
+        }
+
+        let rows = match self.cfg.rules.fall(self.state.score.level) {
+            Fall::Every(frames) => {
+                self.state.fall += 1;
+
+                match self.state.fall >= frames {
+                    true => {
+                        self.state.fall = 0;
+                        1
+                    },
+                    false => 0,
+                }
+            },
+            Fall::Per256(gravity) => {
+                self.state.fall += gravity;
+
+                let rows = self.state.fall / 256;
+                self.state.fall %= 256;
+                rows
+            },
         };
 
-        // collision checking
-        let is_colliding_down = |&[column, row]: &[usize; 2]| -> bool {
-            row + 1 >= 20 || !is_locked(column, row) && is_locked(column, row + 1)
+        let was_grounded = self.is_grounded();
+        let mut moved = 0;
+
+        while moved < rows && !self.is_grounded() {
+            self.advance();
+            moved += 1;
+        }
+
+        self.fallen = moved;
+
+        if !self.is_grounded() {
+            self.state.phase = Phase::Falling;
+            return;
+        }
+
+        // moving down a row restarts the lock delay
+        let grounded = match self.state.phase {
+            Phase::Locking(frames) if moved == 0 => frames + 1,
+            _ => 1,
         };
 
-        let rows_to_clear: Vec<usize> = (0..20)
-            .filter(|&r| (0..10).all(|c| is_locked(c, r)))
-            .collect();
+        let lock = match self.timings().lock {
+            // lock on the next row of gravity
+            None => was_grounded && rows > 0,
+            Some(delay) => grounded >= delay,
+        };
+
+        match lock {
+            true => self.lock(),
+            false => self.state.phase = Phase::Locking(grounded),
+        }
+    }
 
-        if !rows_to_clear.is_empty() {
-            rows_to_clear.into_iter().for_each(|i| self.clear(i));
-        } else if self
+    fn is_grounded(&self) -> bool {
+        self.state
             .focused_shape
             .as_ref() // Option<Shape> -> Option<&Shape>
-            .unwrap() // Option<&Shape> -> &Shape
-            .mino_pos // [[usize; 2]; 4]
-            .iter()
-            .any(is_colliding_down)
-        {
-            self.lock();
-        } else {
-            self.advance();
+            .map(|m| !self.fits(m.variant, m.orientation, [m.origin[0], m.origin[1] + 1]))
+            .unwrap_or(false)
+    }
+
+    /// the shape has room with its layout box at `origin`
+    fn fits(&self, variant: ShapeVariant, orientation: usize, origin: [i32; 2]) -> bool {
+        self.cfg.pieces[variant]
+            .cells(orientation, origin)
+            .all(|pos| !self.is_blocked(pos))
+    }
+
+    /// side of the square of cells a mino covers, 2 in big mode
+    pub fn scale(&self) -> usize {
+        self.cfg.scale()
+    }
+
+    /// columns and rows of the board in minos, 5x10 in big mode
+    pub fn size(&self) -> (i32, i32) {
+        self.cfg.size()
+    }
+
+    /// outside of the board, or a locked mino is in the way
+    fn is_blocked(&self, [c, r]: [i32; 2]) -> bool {
+        let (columns, rows) = self.size();
+
+        if !(0..columns).contains(&c) || !(0..rows).contains(&r) {
+            return true;
         }
+
+        cells(self.scale(), [c as usize, r as usize])
+            .any(|[c, r]| self.state.minos[r][c].map(|m| m.locked).unwrap_or(false))
     }
 
-    pub fn request_turn(&mut self, dir: MinoDirection, stop: &mut bool) {
-        if self.focused_shape.is_none() {
+    fn fill(&mut self, pos: [usize; 2], mino: Option<Mino>) {
+        for [column, row] in cells(self.scale(), pos) {
+            self.state.minos[row][column] = mino;
+        }
+    }
+
+    pub fn timings(&self) -> Timings {
+        self.cfg.timings(self.state.score.level)
+    }
+
+    /// keep a move for the next shape when there's none to make it with
+    fn buffer(&mut self, input: Input) -> bool {
+        let waiting = matches!(self.state.phase, Phase::LineClear(_) | Phase::Entry(_));
+
+        // keys still held when the shape spawns take effect then instead
+        let initial = match input {
+            Input::Turn(_) => false,
+            Input::Rotate(_) => self.cfg.rules.irs,
+            Input::Hold => self.cfg.rules.ihs,
+        };
+
+        if waiting && !initial && !self.is_over() {
+            self.state.buffered.push(input);
+        }
+
+        waiting
+    }
+
+    fn replay_buffered(&mut self) {
+        for input in std::mem::take(&mut self.state.buffered) {
+            match input {
+                Input::Turn(dir) => self.request_turn(dir),
+                Input::Rotate(rot) => self.request_rotate(rot),
+                Input::Hold => self.request_hold(),
+            }
+        }
+    }
+
+    /// delay before a held direction repeats, and the time between repeats
+    pub fn das(&self) -> (Duration, Duration) {
+        let rules = &self.cfg.rules;
+        (rules.frames(self.timings().das), rules.frames(rules.das_repeat))
+    }
+
+    fn request_turn(&mut self, dir: MinoDirection) {
+        if self.buffer(Input::Turn(dir)) {
+            return;
+        }
+
+        if self.state.focused_shape.is_none() || self.is_over() {
             return; // skip
         }
 
@@ -106,166 +579,708 @@ impl Tetris {
             MinoDirection::Right => 1,
         };
 
-        let is_locked = |c: i32, r: i32| -> bool {
-            let (c, r) = (c as usize, r as usize);
-            self.minos[r][c].map(|m| m.locked).unwrap_or(false)
-        };
+        let m = self.state.focused_shape.as_ref().unwrap();
+        let [x, y] = m.origin;
 
-        let is_colliding = |[c, r]: [i32; 2]| -> bool {
-            !(0..10).contains(&(c + offset)) || !is_locked(c, r) && is_locked(c + offset, r)
-        };
-
-        if !self
-            .focused_shape
-            .as_ref()
-            .unwrap()
-            .mino_pos
-            .iter()
-            .map(|&p| [p[0] as i32, p[1] as i32]) // [usize; 2] -> [i32; 2]
-            .any(is_colliding)
-        {
-            // prevent from updating right after this iteration
-            *stop = true;
+        if self.fits(m.variant, m.orientation, [x + offset, y]) {
+            self.state.last_rotated = false;
             // turn the focused tetromino
             self.turn(offset);
         }
     }
 
-    pub fn request_rotate(&mut self, rot: Rotation, stop: &mut bool) {
-        if self.focused_shape.is_none() {
+    fn request_rotate(&mut self, rot: Rotation) {
+        if self.buffer(Input::Rotate(rot)) {
+            return;
+        }
+
+        if self.state.focused_shape.is_none() || self.is_over() {
             return; // skip
         }
 
-        if self.focused_shape.as_ref().unwrap().variant == ShapeVariant::O {
+        let shape = self.state.focused_shape.as_ref().unwrap();
+        let piece = &self.cfg.pieces[shape.variant];
+
+        // collision checking
+        let is_free = |pos: [i32; 2]| -> bool { !self.is_blocked(pos) };
+
+        let rotated = self.cfg.rules.rotation.system().rotate(
+            piece,
+            shape.orientation,
+            shape.origin,
+            rot,
+            &is_free,
+        );
+
+        if let Some((origin, orientation)) = rotated {
+            let [x, y] = shape.origin;
+
+            self.state.last_rotated = true;
+            self.state.last_kick = [origin[0] - x, origin[1] - y];
+            self.move_to(origin, orientation);
+        }
+    }
+
+    fn request_hold(&mut self) {
+        if self.buffer(Input::Hold) {
             return;
         }
 
-        let new_pos = self.focused_shape.as_ref().unwrap().rotate(rot);
+        if self.state.focused_shape.is_none() || self.is_over() {
+            return; // skip
+        }
+
+        // once per shape, and never in a drill
+        if !self.cfg.rules.hold || !self.state.can_hold || self.state.drill.is_some() {
+            return;
+        }
 
-        // is locked
-        let is_locked = |c: i32, r: i32| -> bool {
-            let (c, r) = (c as usize, r as usize);
-            self.minos[r][c].map(|m| m.locked).unwrap_or(false)
+        let next = match self.state.hold {
+            Some(held) => held,
+            None => match self.next_variant() {
+                Some(variant) => variant,
+                None => return, // nothing to swap with
+            },
         };
 
-        // collision checking
-        let is_colliding = |(i, [c, r]): (usize, [i32; 2])| -> bool {
-            let [nc, nr] = new_pos[i];
+        let m = self.state.focused_shape.take().unwrap();
+
+        for &pos in m.mino_pos.iter() {
+            self.fill(pos, None);
+        }
+
+        self.state.hold = Some(m.variant);
+        self.state.can_hold = false;
+        self.state.last_rotated = false;
+        self.spawn(next);
+    }
+
+    /// shapes shown in the preview, in the order they come
+    pub fn upcoming(&self) -> impl Iterator<Item = &ShapeVariant> {
+        let pieces = self.state.sequence.as_ref().unwrap_or(&self.state.queue);
+        pieces.iter().take(self.cfg.rules.previews)
+    }
+
+    /// score the full rows and mark them for removal, false if there are none
+    fn clear_rows(&mut self) -> bool {
+        let is_full =
+            |row: &[Option<Mino>; 10]| row.iter().all(|m| m.map(|m| m.locked).unwrap_or(false));
+        let has_garbage = |row: &[Option<Mino>; 10]| row.iter().flatten().any(|m| m.garbage);
+
+        let rows_to_clear: Vec<usize> =
+            (0..20).filter(|&r| is_full(&self.state.minos[r])).collect();
+
+        if rows_to_clear.is_empty() {
+            self.state.score.break_combo();
+            return false;
+        }
+
+        // a line is as tall as a mino, rows of cells go in pairs in big mode
+        let scale = self.scale();
+
+        let garbage_rows = rows_to_clear
+            .iter()
+            .filter(|&&r| has_garbage(&self.state.minos[r]))
+            .count()
+            .div_ceil(scale) as u32;
+
+        let perfect_clear = (0..20)
+            .filter(|r| !rows_to_clear.contains(r))
+            .all(|r| self.state.minos[r].iter().all(Option::is_none));
 
-            (!(0..10).contains(&nc) || !(0..20).contains(&nr))
-                || (!is_locked(c, r) && is_locked(nc, nr))
+        let clear = LineClear {
+            lines: rows_to_clear.len().div_ceil(scale),
+            spin: self.state.spin,
+            perfect_clear,
         };
 
-        if !self
-            .focused_shape
+        if perfect_clear {
+            self.state.perfect_clear_at = Some(self.state.elapsed);
+        }
+
+        self.state.score.award(clear, &self.cfg.rules);
+        self.state.stats.cleared(clear, &self.state.score);
+        self.state.score.garbage_lines += garbage_rows;
+        self.state.last_clear = Some(clear);
+        self.state.clearing = rows_to_clear;
+
+        if let Some(garbage) = self.state.garbage.as_mut() {
+            garbage.left = garbage.left.saturating_sub(garbage_rows);
+        }
+
+        self.update_master();
+        true
+    }
+
+    /// remove the rows marked by `clear_rows`
+    fn collapse(&mut self) {
+        let rows_to_clear = std::mem::take(&mut self.state.clearing);
+        rows_to_clear.into_iter().for_each(|i| self.clear(i));
+
+        self.check_objective();
+
+        if self
+            .state
+            .garbage
             .as_ref()
-            .unwrap()
-            .mino_pos
-            .iter()
-            .map(|&p| [p[0] as i32, p[1] as i32]) // [usize; 2] -> [i32; 2]
-            .enumerate()
-            .any(is_colliding)
+            .map(|g| g.left == 0)
+            .unwrap_or(false)
         {
-            *stop = true;
-            self.rotate(new_pos);
+            self.finish(Outcome::Cleared);
+        }
+
+        if let Mode::Sprint { lines } = self.cfg.mode {
+            if self.state.score.lines >= lines {
+                self.finish(Outcome::Finished);
+            }
+        }
+
+        if self.state.master.is_some() && self.state.score.level >= master::MAX_LEVEL {
+            self.finish(Outcome::Completed);
+        }
+    }
+
+    fn update_master(&mut self) {
+        if let Some(master) = self.state.master.as_mut() {
+            master.update(self.state.score.level, self.state.score.points, self.state.elapsed);
+        }
+    }
+
+    fn check_objective(&mut self) {
+        let Some(objective) = self.cfg.mode.objective() else {
+            return;
+        };
+
+        let solved = match objective {
+            Objective::Lines(lines) => self.state.score.lines >= lines,
+            Objective::PerfectClear => self
+                .state
+                .last_clear
+                .map(|c| c.perfect_clear)
+                .unwrap_or(false),
+            Objective::TSpin(lines) => self
+                .state
+                .last_clear
+                .map(|c| c.spin.map(|s| s.piece == 'T').unwrap_or(false) && c.lines == lines)
+                .unwrap_or(false),
+        };
+
+        if solved {
+            self.finish(Outcome::Solved);
+        }
+    }
+
+    fn rise_garbage(&mut self) {
+        let scale = self.scale();
+        let columns = self.size().0 as usize;
+
+        let Some(garbage) = self.state.garbage.as_mut() else {
+            return;
+        };
+
+        let on_board = self
+            .state
+            .minos
+            .iter()
+            .filter(|row| row.iter().flatten().any(|m| m.garbage))
+            .count()
+            .div_ceil(scale) as u32;
+
+        let mut topped_out = false;
+
+        // keep the board topped up as the player digs
+        for _ in on_board..garbage.left.min(garbage::VISIBLE_ROWS) {
+            let hole = garbage.next_hole(&mut self.state.rng, columns);
+
+            // a garbage line is as tall as a mino
+            for _ in 0..scale {
+                if self.state.minos[0].iter().any(Option::is_some) {
+                    topped_out = true;
+                    break;
+                }
+
+                for row in 0..19 {
+                    self.state.minos[row] = self.state.minos[row + 1];
+                }
+
+                for (column, mino) in self.state.minos[19].iter_mut().enumerate() {
+                    *mino = (column / scale != hole).then_some(Mino {
+                        locked: true,
+                        garbage: true,
+                        locked_at: self.state.elapsed,
+                        color: garbage::COLOR,
+                    });
+                }
+            }
+        }
+
+        if topped_out {
+            self.finish(Outcome::TopOut);
         }
     }
 
     fn generate(&mut self) {
-        let mino_types = [
-            ShapeVariant::O,
-            ShapeVariant::I,
-            ShapeVariant::Z,
-            ShapeVariant::S,
-            ShapeVariant::J,
-            ShapeVariant::L,
-        ];
+        match self.next_variant() {
+            Some(variant) => self.spawn(variant),
+            // the sequence ran out
+            None => self.finish(Outcome::Failed),
+        }
 
-        // pick a random shape
-        let variant = mino_types[self.rng.gen_range(0..6)];
-
-        // initial positions
-        let mino_pos = match variant {
-            ShapeVariant::S => [[4, 0], [4, 1], [5, 1], [5, 2]],
-            ShapeVariant::Z => [[5, 0], [5, 1], [4, 1], [4, 2]],
-            ShapeVariant::O => [[4, 0], [5, 0], [4, 1], [5, 1]],
-            ShapeVariant::I => [[3, 0], [4, 0], [5, 0], [6, 0]],
-            ShapeVariant::L => [[4, 1], [4, 0], [5, 0], [6, 0]],
-            ShapeVariant::J => [[4, 0], [5, 0], [6, 0], [6, 1]],
+        if self.state.focused_shape.is_none() {
+            return;
+        }
+
+        // keys held through the entry delay swap or turn the shape as it appears
+        if self.cfg.rules.ihs && self.state.held.hold {
+            self.request_hold();
+        }
+
+        if let (true, Some(rot)) = (self.cfg.rules.irs, self.state.held.rotation) {
+            self.request_rotate(rot);
+            self.state.last_rotated = false;
+        }
+
+        // a new place to put the shape, unless it's trying again
+        if let Some(variant) = self.state.focused_shape.as_ref().map(|m| m.variant) {
+            if self
+                .state
+                .drill
+                .as_ref()
+                .map(|d| d.target.is_none())
+                .unwrap_or(false)
+            {
+                let target = self.pick_target(variant);
+                self.state.drill.as_mut().unwrap().target = Some(target);
+            }
+        }
+    }
+
+    /// somewhere on the floor of an empty board for the drill
+    fn pick_target(&mut self, variant: ShapeVariant) -> Target {
+        let (columns, rows) = self.size();
+        let orientation = self.state.rng.below(4);
+
+        let layout = &self.cfg.pieces[variant].layouts[orientation];
+        let left = layout.iter().map(|&[c, _]| c).min().unwrap_or(0);
+        let right = layout.iter().map(|&[c, _]| c).max().unwrap_or(0);
+        let bottom = layout.iter().map(|&[_, r]| r).max().unwrap_or(0);
+
+        Target {
+            orientation,
+            origin: [
+                -left + self.state.rng.below((columns - right + left) as usize) as i32,
+                rows - 1 - bottom,
+            ],
+        }
+    }
+
+    /// board cells of the drill target, more than one per mino when scaled up
+    pub fn target_cells(&self) -> Vec<[usize; 2]> {
+        let (Some(m), Some(target)) = (
+            self.state.focused_shape.as_ref(),
+            self.state.drill.as_ref().and_then(|d| d.target),
+        ) else {
+            return Vec::new();
+        };
+
+        self.cfg.pieces[m.variant]
+            .cells(target.orientation, target.origin)
+            .flat_map(|[c, r]| cells(self.scale(), [c as usize, r as usize]))
+            .collect()
+    }
+
+    fn next_variant(&mut self) -> Option<ShapeVariant> {
+        // take the next shape of the sequence
+        if let Some(sequence) = self.state.sequence.as_mut() {
+            return sequence.pop_front();
+        }
+
+        let variant = self.state.queue.pop_front().unwrap_or_else(|| self.roll());
+        self.fill_queue();
+
+        Some(variant)
+    }
+
+    fn roll(&mut self) -> ShapeVariant {
+        let variant = self.cfg.rules.randomizer.next(
+            &mut self.state.rng,
+            &self.state.history,
+            &self.cfg.pieces,
+        );
+
+        self.state.history.push_back(variant);
+
+        if self.state.history.len() > randomizer::HISTORY {
+            self.state.history.pop_front();
+        }
+
+        variant
+    }
+
+    fn fill_queue(&mut self) {
+        while self.state.queue.len() < self.cfg.rules.previews {
+            let variant = self.roll();
+            self.state.queue.push_back(variant);
+        }
+    }
+
+    /// where the layout box of a piece starts out on this board
+    fn spawn_origin(&self, piece: &Piece) -> [i32; 2] {
+        let columns = self.size().0;
+
+        // keep the shape centered on a narrower board
+        let [x, y] = piece.spawn;
+        let mut origin = [x - (10 - columns) / 2, y];
+
+        // and on the board at all, for wide shapes
+        let left = piece.cells(0, origin).map(|[c, _]| c).min().unwrap_or(0);
+        let right = piece.cells(0, origin).map(|[c, _]| c).max().unwrap_or(0);
+        origin[0] += (-left).max(0) - (right - (columns - 1)).max(0);
+
+        origin
+    }
+
+    fn spawn(&mut self, variant: ShapeVariant) {
+        let piece = &self.cfg.pieces[variant];
+        let origin = self.spawn_origin(piece);
+
+        let mino_pos: Vec<[usize; 2]> = piece
+            .cells(0, origin)
+            .map(|[c, r]| [c as usize, r as usize])
+            .collect();
+
+        // no room left for the new shape
+        let is_taken = |&pos: &[usize; 2]| -> bool {
+            cells(self.scale(), pos).any(|[c, r]| self.state.minos[r][c].is_some())
+        };
+
+        if mino_pos.iter().any(is_taken) {
+            self.finish(Outcome::BlockOut);
+            return;
+        }
+
+        let mino = Mino {
+            locked: false,
+            garbage: false,
+            locked_at: Duration::ZERO,
+            color: piece.color,
         };
 
         // fill each position
-        for &[column, row] in mino_pos.iter() {
-            self.minos[row][column] = Some(Mino { locked: false });
+        for &pos in mino_pos.iter() {
+            self.fill(pos, Some(mino));
         }
 
         // focus the formed shape
-        self.focused_shape = Some(Shape { variant, mino_pos });
+        self.state.focused_shape = Some(Shape {
+            variant,
+            mino_pos,
+            orientation: 0,
+            origin,
+        });
+
+        self.state.fall = 0;
+        self.state.score.piece_spawned(&self.cfg.rules);
+        self.update_master();
     }
 
     fn lock(&mut self) {
         // pin focused_shape to this position
-        // consumes self.focused_shape
-        let m = self.focused_shape.take().unwrap();
+        // consumes self.state.focused_shape
+        let m = self.state.focused_shape.take().unwrap();
+
+        // before the shape becomes part of the stack it's checked against
+        self.state.spin = match self.state.last_rotated {
+            true => self.spin(&m),
+            false => None,
+        };
 
-        for &[column, row] in m.mino_pos.iter() {
-            let mino = &mut self.minos[row][column];
+        self.state.stats.locked(m.variant);
 
-            mino.as_mut().unwrap().locked = true;
+        let fault = self.judge_finesse(&m);
+
+        if self.state.drill.is_some() {
+            self.finish_drill(m, fault);
+            return;
+        }
+
+        for &pos in m.mino_pos.iter() {
+            for [column, row] in cells(self.scale(), pos) {
+                let mino = self.state.minos[row][column].as_mut().unwrap();
+
+                mino.locked = true;
+                mino.locked_at = self.state.elapsed;
+            }
         }
+
+        self.state.can_hold = true;
+
+        self.state.last_rotated = false;
+
+        self.state.phase = match self.clear_rows() {
+            true => Phase::LineClear(self.timings().line_clear),
+            false => Phase::Entry(self.timings().are),
+        };
+
+        self.locked();
     }
 
-    fn advance(&mut self) {
-        // move focused_shape 1 block down
-        let m = self.focused_shape.as_mut().unwrap();
+    // every shape that locks is checked, and kept in practice to go back to
+    fn locked(&mut self) {
+        self.checks.push(self.check());
 
-        for &[column, row] in m.mino_pos.iter() {
-            self.minos[row][column] = None;
+        if let Some(mut practice) = self.practice.take() {
+            practice.locked(self);
+            self.practice = Some(practice);
         }
+    }
 
-        for pos in m.mino_pos.iter_mut() {
-            pos[1] += 1;
-            self.minos[pos[1]][pos[0]] = Some(Mino { locked: false });
+    /// take back the last placement in practice
+    pub fn undo(&mut self) {
+        if let Some(at) = self.practice.as_ref().and_then(|p| p.at.checked_sub(1)) {
+            self.rewind_to(at);
         }
     }
 
-    fn turn(&mut self, offset: i32) {
-        let m = self.focused_shape.as_mut().unwrap();
+    /// place the shape taken back last again
+    pub fn redo(&mut self) {
+        if let Some(at) = self.practice.as_ref().map(|p| p.at + 1) {
+            self.rewind_to(at);
+        }
+    }
+
+    /// go back, or forward again, to how the game was after the `at`th shape locked
+    pub fn rewind_to(&mut self, at: usize) {
+        if let Some(mut practice) = self.practice.take() {
+            practice.restore(at, self);
+            self.practice = Some(practice);
+        }
+    }
+
+    /// whether the shape took more keys than needed to get where it locked
+    fn judge_finesse(&mut self, m: &Shape) -> bool {
+        let keys = std::mem::take(&mut self.state.piece_keys);
+
+        // a held shape starts over somewhere else
+        if !self.state.can_hold {
+            return false;
+        }
+
+        // spins and tucks go under the stack, which the shortest path from spawn doesn't know
+        let tucked = m
+            .mino_pos
+            .iter()
+            .any(|&[c, r]| (0..r as i32).any(|above| self.is_blocked([c as i32, above])));
+
+        if self.state.spin.is_some() || tucked {
+            return false;
+        }
+
+        let piece = &self.cfg.pieces[m.variant];
+        let placed: Vec<[i32; 2]> = m
+            .mino_pos
+            .iter()
+            .map(|&[c, r]| [c as i32, r as i32])
+            .collect();
+
+        let least = finesse::min_keys(
+            piece,
+            self.cfg.rules.rotation.system(),
+            self.spawn_origin(piece),
+            self.size().0,
+            &placed,
+        );
+
+        let fault = least.map(|least| keys > least).unwrap_or(false);
+
+        if fault {
+            self.state.stats.finesse_faults += 1;
+            self.state.fault_at = Some(self.state.elapsed);
+        }
+
+        fault
+    }
+
+    /// the drill board stays empty, and a shape comes back until it's placed right
+    fn finish_drill(&mut self, m: Shape, fault: bool) {
+        for &pos in m.mino_pos.iter() {
+            self.fill(pos, None);
+        }
+
+        let piece = &self.cfg.pieces[m.variant];
+        let drill = self.state.drill.as_mut().unwrap();
+
+        let mut placed = m.mino_pos.clone();
+        placed.sort();
+
+        let mut wanted: Vec<[usize; 2]> = drill
+            .target
+            .iter()
+            .flat_map(|t| piece.cells(t.orientation, t.origin))
+            .map(|[c, r]| [c as usize, r as usize])
+            .collect();
+        wanted.sort();
 
-        for &[column, row] in m.mino_pos.iter() {
-            self.minos[row][column] = None;
+        match placed == wanted && !fault {
+            true => {
+                drill.target = None;
+                drill.streak += 1;
+            },
+            false => {
+                drill.streak = 0;
+                self.state.queue.push_front(m.variant);
+            },
         }
 
-        for pos in m.mino_pos.iter_mut() {
-            pos[0] = (pos[0] as i32 + offset) as usize;
-            self.minos[pos[1]][pos[0]] = Some(Mino { locked: false });
+        self.state.can_hold = true;
+        self.state.last_rotated = false;
+        self.state.phase = Phase::Entry(self.timings().are);
+
+        self.locked();
+    }
+
+    /// the spin of a shape that was turned into place, if any
+    fn spin(&self, m: &Shape) -> Option<Spin> {
+        let piece = &self.cfg.pieces[m.variant];
+        let rule = self.cfg.rules.spins;
+
+        if rule == SpinRule::None {
+            return None;
         }
+
+        if piece.name == 'T' {
+            return self.t_spin(m);
+        }
+
+        // shapes that look the same every way can't spin
+        if rule == SpinRule::TSpins || piece.layouts.iter().all(|l| *l == piece.layouts[0]) {
+            return None;
+        }
+
+        let [x, y] = m.origin;
+        let stuck = [[-1, 0], [1, 0], [0, -1]]
+            .iter()
+            .all(|&[dc, dr]| !self.fits(m.variant, m.orientation, [x + dc, y + dr]));
+
+        let kind = match rule {
+            SpinRule::All => SpinKind::Full,
+            _ => SpinKind::Mini,
+        };
+
+        stuck.then_some(Spin {
+            piece: piece.name,
+            kind,
+        })
     }
 
-    fn rotate(&mut self, new_pos: [[i32; 2]; 4]) {
-        let m = self.focused_shape.as_mut().unwrap();
+    /// three corners around the center of the t taken, full with both in front of it
+    fn t_spin(&self, m: &Shape) -> Option<Spin> {
+        let minos: Vec<[i32; 2]> = m
+            .mino_pos
+            .iter()
+            .map(|&[c, r]| [c as i32, r as i32])
+            .collect();
+        let is_mino = |[c, r]: [i32; 2]| minos.contains(&[c, r]);
+
+        // the one mino touching the three others
+        let touching = |&&[c, r]: &&[i32; 2]| {
+            [[-1, 0], [1, 0], [0, -1], [0, 1]]
+                .iter()
+                .filter(|&&[dc, dr]| is_mino([c + dc, r + dr]))
+                .count()
+        };
+
+        let [column, row] = *minos.iter().max_by_key(touching).unwrap();
+
+        let corners = [[-1, -1], [1, -1], [-1, 1], [1, 1]]
+            .iter()
+            .filter(|&&[dc, dr]| self.is_blocked([column + dc, row + dr]))
+            .count();
 
-        for &[column, row] in m.mino_pos.iter() {
-            self.minos[row][column] = None;
+        if corners < 3 {
+            return None;
         }
 
-        for (i, pos) in m.mino_pos.iter_mut().enumerate() {
-            pos[0] = new_pos[i][0] as usize;
-            pos[1] = new_pos[i][1] as usize;
-            self.minos[pos[1]][pos[0]] = Some(Mino { locked: false });
+        // the t points where the center has a mino on one side but not the other
+        let [dc, dr] = [[-1, 0], [1, 0], [0, -1], [0, 1]]
+            .into_iter()
+            .find(|&[dc, dr]| is_mino([column + dc, row + dr]) && !is_mino([column - dc, row - dr]))
+            .unwrap_or([0, -1]);
+
+        let front = [
+            [column + dc - dr, row + dr - dc],
+            [column + dc + dr, row + dr + dc],
+        ];
+
+        // the long kick that fits a t into a t-spin triple slot makes a full spin either way
+        let kind = match front.iter().all(|&pos| self.is_blocked(pos))
+            || matches!(self.state.last_kick, [-1 | 1, -2 | 2])
+        {
+            true => SpinKind::Full,
+            false => SpinKind::Mini,
+        };
+
+        Some(Spin { piece: 'T', kind })
+    }
+
+    fn advance(&mut self) {
+        self.state.last_rotated = false;
+
+        // move focused_shape 1 block down
+        let m = self.state.focused_shape.as_ref().unwrap();
+        let [x, y] = m.origin;
+
+        self.move_to([x, y + 1], m.orientation);
+    }
+
+    fn turn(&mut self, offset: i32) {
+        let m = self.state.focused_shape.as_ref().unwrap();
+        let [x, y] = m.origin;
+
+        self.move_to([x + offset, y], m.orientation);
+    }
+
+    fn move_to(&mut self, origin: [i32; 2], orientation: usize) {
+        let m = self.state.focused_shape.as_ref().unwrap();
+        let piece = &self.cfg.pieces[m.variant];
+
+        let old_pos = m.mino_pos.clone();
+        let new_pos: Vec<[usize; 2]> = piece
+            .cells(orientation, origin)
+            .map(|[c, r]| [c as usize, r as usize])
+            .collect();
+
+        let mino = Mino {
+            locked: false,
+            garbage: false,
+            locked_at: Duration::ZERO,
+            color: piece.color,
+        };
+
+        for &pos in old_pos.iter() {
+            self.fill(pos, None);
         }
+
+        for &pos in new_pos.iter() {
+            self.fill(pos, Some(mino));
+        }
+
+        let m = self.state.focused_shape.as_mut().unwrap();
+        m.mino_pos = new_pos;
+        m.orientation = orientation;
+        m.origin = origin;
     }
 
     fn clear(&mut self, row_index: usize) {
-        for mino in self.minos[row_index].iter_mut() {
+        for mino in self.state.minos[row_index].iter_mut() {
             *mino = None;
         }
 
-        for row in (0..row_index).into_iter().rev() {
-            self.minos[row + 1] = self.minos[row];
+        for row in (0..row_index).rev() {
+            self.state.minos[row + 1] = self.state.minos[row];
         }
 
-        for mino in self.minos[0].iter_mut() {
+        for mino in self.state.minos[0].iter_mut() {
             *mino = None;
         }
     }
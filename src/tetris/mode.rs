@@ -0,0 +1,120 @@
+use std::time::Duration;
+
+use crate::error::Error;
+
+use super::puzzle::Objective;
+use super::puzzle::Puzzle;
+
+pub enum Mode {
+    /// endless play, the game only ends when the stack tops out
+    Marathon,
+    /// score as many points as possible before the time limit runs out
+    Ultra(Duration),
+    /// dig through the given number of garbage lines
+    Dig { lines: u32, messiness: f32 },
+    /// clear the given number of lines as fast as possible
+    Sprint { lines: u32 },
+    /// reach the objective of a puzzle with its fixed pieces
+    Puzzle(Box<Puzzle>),
+    /// climb from level 0 to 999 under tgm rules, graded by score and time
+    Master,
+    /// place shapes where shown with as few keys as possible, on a board that stays empty
+    Drill,
+    /// endless play where placements can be undone, redone and rewound
+    Practice,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    /// the time limit of the mode ran out
+    TimeUp,
+    /// a new shape had no room to spawn
+    BlockOut,
+    /// rising garbage pushed the stack above the board
+    TopOut,
+    /// every garbage line of the mode was cleared
+    Cleared,
+    /// the objective of the puzzle was reached
+    Solved,
+    /// the pieces of the puzzle ran out before reaching the objective
+    Failed,
+    /// the last level of the mode was reached
+    Completed,
+    /// every line of the sprint was cleared
+    Finished,
+}
+
+impl Outcome {
+    pub const ALL: [Outcome; 8] = [
+        Outcome::TimeUp,
+        Outcome::BlockOut,
+        Outcome::TopOut,
+        Outcome::Cleared,
+        Outcome::Solved,
+        Outcome::Failed,
+        Outcome::Completed,
+        Outcome::Finished,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Outcome::TimeUp => "time-up",
+            Outcome::BlockOut => "block-out",
+            Outcome::TopOut => "top-out",
+            Outcome::Cleared => "cleared",
+            Outcome::Solved => "solved",
+            Outcome::Failed => "failed",
+            Outcome::Completed => "completed",
+            Outcome::Finished => "finished",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Result<Self, Error> {
+        Self::ALL
+            .into_iter()
+            .find(|o| o.name() == name)
+            .ok_or(Error::Config(format!("Unknown outcome `{name}`.")))
+    }
+}
+
+impl Mode {
+    pub fn ultra(minutes: u64) -> Result<Self, Error> {
+        match minutes {
+            2 | 3 => Ok(Mode::Ultra(Duration::from_secs(minutes * 60))),
+            _ => Err(Error::Config(format!("Ultra lasts 2 or 3 minutes, not {minutes}."))),
+        }
+    }
+
+    pub fn time_limit(&self) -> Option<Duration> {
+        match self {
+            Mode::Ultra(limit) => Some(*limit),
+            Mode::Marathon
+            | Mode::Dig { .. }
+            | Mode::Sprint { .. }
+            | Mode::Puzzle(_)
+            | Mode::Master
+            | Mode::Drill
+            | Mode::Practice => None,
+        }
+    }
+
+    pub fn objective(&self) -> Option<Objective> {
+        match self {
+            Mode::Puzzle(puzzle) => Some(puzzle.objective),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Mode::Marathon => "marathon",
+            Mode::Ultra(_) => "ultra",
+            Mode::Dig { .. } => "dig",
+            Mode::Sprint { .. } => "sprint",
+            Mode::Puzzle(_) => "puzzle",
+            Mode::Master => "master",
+            Mode::Drill => "drill",
+            Mode::Practice => "practice",
+        }
+    }
+}
@@ -0,0 +1,389 @@
+use std::collections::HashMap;
+use std::ops::Index;
+use std::path::Path;
+use std::fs;
+
+use crate::error::Error;
+
+use super::mino::ShapeVariant;
+
+// a piece set file looks like this, see `pieces/` for complete ones
+//
+//   # comments start with a hash
+//   kick jlstz 0>1 0,0 -1,0 -1,-1 0,2 -1,2
+//   piece T
+//   color 160 0 240
+//   spawn 3 0
+//   kicks jlstz
+//   half tetrio tetrio
+//   layout
+//   .x. .x. ... .x.
+//   xxx .xx xxx xx.
+//   ... .x. .x. .x.
+//
+// offsets are [column, row] with rows going down. `kick <table> <from>><to>`
+// lists the offsets tried in order when turning between two orientations,
+// pieces pick a table with `kicks`, and one for 180 degree turns in each style
+// of those with `half <style> <table>`. a layout has one orientation for shapes
+// that look the same every way, or four side by side, clockwise from the
+// spawn one. `spawn` is the top left of the layout box on the board.
+
+/// the sets shipped with the game, by name
+const BUILTIN: [(&str, &str); 5] = [
+    ("tetrominoes", include_str!("../../pieces/tetrominoes.txt")),
+    ("ars", include_str!("../../pieces/ars.txt")),
+    ("nrs", include_str!("../../pieces/nrs.txt")),
+    ("pentominoes", include_str!("../../pieces/pentominoes.txt")),
+    ("trominoes", include_str!("../../pieces/trominoes.txt")),
+];
+
+/// the style of 180 degree kicks played unless another is picked, every set knows it
+/// even without any `half` lines for it
+pub const DEFAULT_HALF: &str = "tetrio";
+
+type Cells = Vec<[i32; 2]>;
+type Kicks = [[Cells; 4]; 4];
+
+#[derive(Debug, Clone)]
+pub struct Piece {
+    pub name: char,
+    pub color: [u8; 3],
+    /// top left of the layout box when the piece spawns
+    pub spawn: [i32; 2],
+    /// cells of each orientation, clockwise from the spawn one
+    pub layouts: [Cells; 4],
+    /// offsets tried in order when turning, by orientation before and after
+    pub kicks: Kicks,
+}
+
+impl Piece {
+    /// board positions of the cells with the layout box at `origin`
+    pub fn cells(
+        &self,
+        orientation: usize,
+        [x, y]: [i32; 2],
+    ) -> impl Iterator<Item = [i32; 2]> + '_ {
+        self.layouts[orientation]
+            .iter()
+            .map(move |&[c, r]| [x + c, y + r])
+    }
+
+    /// offsets to try when turning, just staying in place without a kick table
+    pub fn kicks(&self, from: usize, to: usize) -> &[[i32; 2]] {
+        match self.kicks[from][to].is_empty() {
+            true => &[[0, 0]],
+            false => &self.kicks[from][to],
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PieceSet {
+    pub name: String,
+    pub pieces: Vec<Piece>,
+}
+
+impl Index<ShapeVariant> for PieceSet {
+    type Output = Piece;
+
+    fn index(&self, variant: ShapeVariant) -> &Piece {
+        &self.pieces[variant.0]
+    }
+}
+
+impl Default for PieceSet {
+    fn default() -> Self {
+        Self::load("tetrominoes", DEFAULT_HALF).expect("The built in piece sets are valid.")
+    }
+}
+
+// a piece as it's read, checked once the whole file is
+#[derive(Default)]
+struct Draft<'a> {
+    name: char,
+    color: Option<[u8; 3]>,
+    spawn: Option<[i32; 2]>,
+    kicks: Option<&'a str>,
+    half: Option<&'a str>,
+    rows: Vec<&'a str>,
+}
+
+impl PieceSet {
+    /// a set shipped with the game by name, or else a set file, with the 180 degree kicks
+    /// of the `half` style
+    pub fn load(name: &str, half: &str) -> Result<Self, Error> {
+        if let Some((name, text)) = BUILTIN.iter().find(|(n, _)| *n == name) {
+            return Self::parse(name, text, half);
+        }
+
+        let path = Path::new(name);
+        let text = fs::read_to_string(path)
+            .map_err(Error::io(format!("Couldn't read piece set {}", path.display())))?;
+        let name = path.file_stem().unwrap_or_default().to_string_lossy();
+
+        Self::parse(&name, &text, half)
+            .map_err(|e| Error::Config(format!("Invalid piece set {}: {e}", path.display())))
+    }
+
+    pub fn parse(name: &str, text: &str, half: &str) -> Result<Self, Error> {
+        let mut tables: HashMap<&str, Kicks> = HashMap::new();
+        let mut drafts: Vec<Draft> = Vec::new();
+        let mut in_layout = false;
+        let mut styles = vec![DEFAULT_HALF];
+
+        for line in text.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            // layout rows go on until the next key
+            if in_layout && line.starts_with(['.', 'x']) {
+                drafts.last_mut().unwrap().rows.push(line);
+                continue;
+            }
+
+            in_layout = false;
+
+            let mut words = line.split_whitespace();
+            let key = words.next().unwrap_or_default();
+
+            if key == "kick" {
+                let (table, turn) = match (words.next(), words.next()) {
+                    (Some(table), Some(turn)) => (table, turn),
+                    _ => return Err(Error::Config(format!("Incomplete kick `{line}`."))),
+                };
+
+                let [from, to] = parse_turn(turn)?;
+                let offsets = words.map(parse_offset).collect::<Result<_, _>>()?;
+
+                tables.entry(table).or_default()[from][to] = offsets;
+                continue;
+            }
+
+            if key == "piece" {
+                let name = match words.next().map(|w| w.chars().collect::<Vec<_>>()) {
+                    Some(chars) if chars.len() == 1 => chars[0],
+                    _ => {
+                        return Err(Error::Config(format!(
+                            "A piece is named by one letter, not `{line}`."
+                        )))
+                    },
+                };
+
+                drafts.push(Draft {
+                    name,
+                    ..Draft::default()
+                });
+                continue;
+            }
+
+            let Some(draft) = drafts.last_mut() else {
+                return Err(Error::Config(format!("`{key}` comes before any piece.")));
+            };
+
+            let values: Vec<&str> = words.collect();
+
+            match key {
+                "color" => {
+                    let parse = |v: &str| {
+                        v.parse()
+                            .map_err(|e| Error::Config(format!("Invalid color: {e}")))
+                    };
+
+                    draft.color = match values[..] {
+                        [r, g, b] => Some([parse(r)?, parse(g)?, parse(b)?]),
+                        _ => {
+                            return Err(Error::Config(format!(
+                                "A color is red, green and blue, not `{line}`."
+                            )))
+                        },
+                    };
+                },
+                "spawn" => {
+                    let parse = |v: &str| {
+                        v.parse()
+                            .map_err(|e| Error::Config(format!("Invalid spawn: {e}")))
+                    };
+
+                    draft.spawn = match values[..] {
+                        [c, r] => Some([parse(c)?, parse(r)?]),
+                        _ => {
+                            return Err(Error::Config(format!(
+                                "A spawn is a column and a row, not `{line}`."
+                            )))
+                        },
+                    };
+                },
+                "kicks" => draft.kicks = values.first().copied(),
+                "half" => match values[..] {
+                    [style, table] => {
+                        if !styles.contains(&style) {
+                            styles.push(style);
+                        }
+
+                        if style == half {
+                            draft.half = Some(table);
+                        }
+                    },
+                    _ => {
+                        return Err(Error::Config(format!(
+                            "A half kick is a style and a table, not `{line}`."
+                        )))
+                    },
+                },
+                "layout" => in_layout = true,
+                other => return Err(Error::Config(format!("Unknown key `{other}`."))),
+            }
+        }
+
+        if !styles.contains(&half) {
+            return Err(Error::Config(format!(
+                "Unknown half kick style `{half}`, the {name} set has `{}`.",
+                styles.join("`, `")
+            )));
+        }
+
+        let mut pieces: Vec<Piece> = Vec::new();
+
+        for draft in drafts {
+            if pieces.iter().any(|p| p.name == draft.name) {
+                return Err(Error::Config(format!("There are two pieces named `{}`.", draft.name)));
+            }
+
+            let table = |name: Option<&str>| match name {
+                Some(name) => tables
+                    .get(name)
+                    .cloned()
+                    .ok_or(Error::Config(format!("Unknown kick table `{name}`."))),
+                None => Ok(Kicks::default()),
+            };
+
+            let mut kicks = table(draft.kicks)?;
+
+            // the 180 degree turns of the half table
+            let half_kicks = table(draft.half)?;
+
+            for from in 0..4 {
+                let to = (from + 2) % 4;
+
+                if !half_kicks[from][to].is_empty() {
+                    kicks[from][to] = half_kicks[from][to].clone();
+                }
+            }
+
+            let piece = Piece {
+                name: draft.name,
+                color: draft.color.unwrap_or([255, 255, 255]),
+                spawn: draft.spawn.unwrap_or([3, 0]),
+                layouts: parse_layout(&draft.rows).map_err(|e| {
+                    Error::Config(format!("Invalid layout of `{}`: {e}", draft.name))
+                })?,
+                kicks,
+            };
+
+            // the piece must start out on the board
+            if piece
+                .cells(0, piece.spawn)
+                .any(|[c, r]| !(0..10).contains(&c) || !(0..20).contains(&r))
+            {
+                return Err(Error::Config(format!(
+                    "`{}` spawns outside of the board.",
+                    piece.name
+                )));
+            }
+
+            pieces.push(piece);
+        }
+
+        if pieces.is_empty() {
+            return Err(Error::Config("The set has no pieces.".into()));
+        }
+
+        Ok(Self {
+            name: name.to_string(),
+            pieces,
+        })
+    }
+
+    pub fn variants(&self) -> impl Iterator<Item = ShapeVariant> {
+        (0..self.pieces.len()).map(ShapeVariant)
+    }
+
+    /// the piece with this name, ignoring case when none matches exactly
+    pub fn find(&self, name: char) -> Option<ShapeVariant> {
+        let position = |same: &dyn Fn(char) -> bool| self.pieces.iter().position(|p| same(p.name));
+
+        position(&|c| c == name)
+            .or_else(|| position(&|c| c.eq_ignore_ascii_case(&name)))
+            .map(ShapeVariant)
+    }
+}
+
+// e.g `0>1`
+fn parse_turn(turn: &str) -> Result<[usize; 2], Error> {
+    let parse = |o: &str| match o.parse() {
+        Ok(o) if o < 4 => Ok(o),
+        _ => Err(Error::Config(format!("Invalid orientation `{o}` in `{turn}`."))),
+    };
+
+    match turn.split_once('>') {
+        Some((from, to)) => Ok([parse(from)?, parse(to)?]),
+        None => Err(Error::Config(format!("Invalid turn `{turn}`, e.g `0>1`."))),
+    }
+}
+
+// e.g `-1,2`
+fn parse_offset(offset: &str) -> Result<[i32; 2], Error> {
+    let parse = |v: &str| {
+        v.parse()
+            .map_err(|e| Error::Config(format!("Invalid offset `{offset}`: {e}")))
+    };
+
+    match offset.split_once(',') {
+        Some((c, r)) => Ok([parse(c)?, parse(r)?]),
+        None => Err(Error::Config(format!("Invalid offset `{offset}`, e.g `-1,2`."))),
+    }
+}
+
+fn parse_layout(rows: &[&str]) -> Result<[Cells; 4], Error> {
+    let mut layouts: [Cells; 4] = Default::default();
+    let mut orientations = None;
+
+    for (r, row) in rows.iter().enumerate() {
+        let groups: Vec<&str> = row.split_whitespace().collect();
+
+        if !matches!(groups.len(), 1 | 4)
+            || *orientations.get_or_insert(groups.len()) != groups.len()
+        {
+            return Err(Error::Config(format!(
+                "`{row}` should have 1 or 4 orientations like the other rows."
+            )));
+        }
+
+        for (layout, group) in layouts.iter_mut().zip(groups) {
+            for (c, cell) in group.chars().enumerate() {
+                match cell {
+                    'x' => layout.push([c as i32, r as i32]),
+                    '.' => (),
+                    _ => return Err(Error::Config(format!("Unknown cell `{cell}` in `{row}`."))),
+                }
+            }
+        }
+    }
+
+    // the same in every orientation
+    if orientations == Some(1) {
+        let spawn = layouts[0].clone();
+        layouts.fill(spawn);
+    }
+
+    let size = layouts[0].len();
+
+    if size == 0 || layouts.iter().any(|l| l.len() != size) {
+        return Err(Error::Config(
+            "Every orientation needs the same number of cells.".into(),
+        ));
+    }
+
+    Ok(layouts)
+}
@@ -0,0 +1,80 @@
+use std::time::Instant;
+
+use super::replay::Check;
+use super::replay::Event;
+use super::state::State;
+use super::Tetris;
+
+/// the game as it was right after a shape locked, or when it started or was resumed
+#[derive(Clone)]
+pub struct Snapshot {
+    state: State,
+    /// events since the snapshot before, the log is too long to keep whole every time
+    events: Vec<(u64, Event)>,
+    /// checks since the snapshot before, the one of the shape that locked or none at the start
+    checks: Vec<Check>,
+}
+
+impl Snapshot {
+    fn take(game: &Tetris, (logged, checked): (usize, usize)) -> Self {
+        Self {
+            state: game.state.clone(),
+            events: game.log[logged..].to_vec(),
+            checks: game.checks[checked..].to_vec(),
+        }
+    }
+
+    fn restore(&self, game: &mut Tetris) {
+        game.state = self.state.clone();
+        game.fallen = 0;
+        game.last_update = Instant::now();
+    }
+}
+
+pub struct Practice {
+    /// one for the start of the game, or where it was resumed, and one for every shape locked since
+    pub snapshots: Vec<Snapshot>,
+    /// the snapshot the game went on from
+    pub at: usize,
+}
+
+impl Practice {
+    pub fn new(game: &Tetris) -> Self {
+        Self {
+            snapshots: vec![Snapshot::take(game, (0, 0))],
+            at: 0,
+        }
+    }
+
+    // events and checks kept by the snapshots up to `at`
+    fn kept(&self) -> (usize, usize) {
+        self.snapshots[..=self.at]
+            .iter()
+            .fold((0, 0), |(events, checks), s| {
+                (events + s.events.len(), checks + s.checks.len())
+            })
+    }
+
+    /// a shape locked, the placements undone before it can't be redone anymore
+    pub fn locked(&mut self, game: &Tetris) {
+        let snapshot = Snapshot::take(game, self.kept());
+
+        self.snapshots.truncate(self.at + 1);
+        self.snapshots.push(snapshot);
+        self.at = self.snapshots.len() - 1;
+    }
+
+    /// put the game back the way it was at snapshot `at`
+    pub fn restore(&mut self, at: usize, game: &mut Tetris) {
+        if at >= self.snapshots.len() {
+            return;
+        }
+
+        self.at = at;
+        self.snapshots[at].restore(game);
+
+        let kept = &self.snapshots[..=at];
+        game.log = kept.iter().flat_map(|s| s.events.iter().copied()).collect();
+        game.checks = kept.iter().flat_map(|s| s.checks.iter().copied()).collect();
+    }
+}
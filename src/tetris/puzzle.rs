@@ -0,0 +1,149 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+use std::path::Path;
+use std::fs;
+
+use crate::error::Error;
+
+use super::mino::Mino;
+use super::garbage;
+
+// a puzzle file looks like this, the board is aligned to the bottom
+//
+//   # comments start with a hash
+//   objective t-spin-triple
+//   pieces TI
+//   board
+//   xxxx..xxxx
+//   xxxx...xxx
+//   xxxxx.xxxx
+//
+// filled cells are `x`, or `g` to flag them as garbage, and `.` is empty.
+// pieces are named as in the piece set played. the objective is one of
+// `lines <n>`, `perfect-clear`, `t-spin-single`, `t-spin-double` or
+// `t-spin-triple`.
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Objective {
+    /// clear at least this many lines
+    Lines(u32),
+    /// leave the board completely empty after a clear
+    PerfectClear,
+    /// clear this many lines at once with a t-spin
+    TSpin(usize),
+}
+
+#[derive(Clone)]
+pub struct Puzzle {
+    pub board: [[Option<Mino>; 10]; 20],
+    /// names of the pieces in the piece set
+    pub pieces: VecDeque<char>,
+    pub objective: Objective,
+}
+
+impl Puzzle {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)
+            .map_err(Error::io(format!("Couldn't read puzzle {}", path.display())))?;
+
+        Self::parse(&text)
+            .map_err(|e| Error::Config(format!("Invalid puzzle {}: {e}", path.display())))
+    }
+
+    pub fn parse(text: &str) -> Result<Self, Error> {
+        let mut objective = None;
+        let mut pieces = VecDeque::new();
+        let mut rows = Vec::new();
+        let mut in_board = false;
+
+        for line in text.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            if in_board {
+                rows.push(parse_row(line)?);
+                continue;
+            }
+
+            let mut words = line.split_whitespace();
+
+            match words.next() {
+                Some("objective") => objective = Some(parse_objective(words.collect())?),
+                Some("pieces") => pieces.extend(words.flat_map(str::chars)),
+                Some("board") => in_board = true,
+                Some(other) => return Err(Error::Config(format!("Unknown key `{other}`."))),
+                None => (),
+            }
+        }
+
+        if rows.len() > 20 {
+            return Err(Error::Config(format!(
+                "The board has {} rows, at most 20 fit.",
+                rows.len()
+            )));
+        }
+
+        if pieces.is_empty() {
+            return Err(Error::Config("The puzzle has no pieces.".into()));
+        }
+
+        // align the rows to the bottom of the board
+        let mut board = [[None; 10]; 20];
+        let top = 20 - rows.len();
+
+        for (i, row) in rows.into_iter().enumerate() {
+            board[top + i] = row;
+        }
+
+        Ok(Self {
+            board,
+            pieces,
+            objective: objective.ok_or(Error::Config("The puzzle has no objective.".into()))?,
+        })
+    }
+}
+
+fn parse_objective(words: Vec<&str>) -> Result<Objective, Error> {
+    match words[..] {
+        ["lines", n] => Ok(Objective::Lines(
+            n.parse()
+                .map_err(|e| Error::Config(format!("Invalid lines: {e}")))?,
+        )),
+        ["perfect-clear"] => Ok(Objective::PerfectClear),
+        ["t-spin-single"] => Ok(Objective::TSpin(1)),
+        ["t-spin-double"] => Ok(Objective::TSpin(2)),
+        ["t-spin-triple"] => Ok(Objective::TSpin(3)),
+        _ => Err(Error::Config(format!("Unknown objective `{}`.", words.join(" ")))),
+    }
+}
+
+fn parse_row(line: &str) -> Result<[Option<Mino>; 10], Error> {
+    let mut row = [None; 10];
+
+    if line.chars().count() != 10 {
+        return Err(Error::Config(format!("The row `{line}` is not 10 cells wide.")));
+    }
+
+    for (mino, c) in row.iter_mut().zip(line.chars()) {
+        *mino = match c {
+            '.' => None,
+            'x' => Some(Mino {
+                locked: true,
+                garbage: false,
+                locked_at: Duration::ZERO,
+                color: [0, 0, 255],
+            }),
+            'g' => Some(Mino {
+                locked: true,
+                garbage: true,
+                locked_at: Duration::ZERO,
+                color: garbage::COLOR,
+            }),
+            _ => return Err(Error::Config(format!("Unknown cell `{c}` in `{line}`."))),
+        };
+    }
+
+    Ok(row)
+}
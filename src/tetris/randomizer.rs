@@ -0,0 +1,68 @@
+use super::mino::ShapeVariant;
+use super::pieces::PieceSet;
+use super::rng::Xoshiro256;
+
+use std::collections::VecDeque;
+
+/// shapes remembered by the randomizers
+pub const HISTORY: usize = 4;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Randomizer {
+    /// every shape is equally likely every time
+    Uniform,
+    /// rolls one shape more than there are, or a repeat of the last one, and rerolls once
+    Nes,
+    /// rerolls up to four times to avoid the last four shapes, never starts with s, z or o
+    Tgm,
+}
+
+fn any(rng: &mut Xoshiro256, pieces: &PieceSet) -> ShapeVariant {
+    ShapeVariant(rng.below(pieces.pieces.len()))
+}
+
+impl Randomizer {
+    /// `history` holds the last shapes rolled, the most recent one at the back
+    pub fn next(
+        &self,
+        rng: &mut Xoshiro256,
+        history: &VecDeque<ShapeVariant>,
+        pieces: &PieceSet,
+    ) -> ShapeVariant {
+        match self {
+            Randomizer::Uniform => any(rng, pieces),
+            Randomizer::Nes => {
+                let roll = rng.below(pieces.pieces.len() + 1);
+
+                match roll < pieces.pieces.len() && Some(&ShapeVariant(roll)) != history.back() {
+                    true => ShapeVariant(roll),
+                    false => any(rng, pieces),
+                }
+            },
+            Randomizer::Tgm if history.is_empty() => {
+                let first: Vec<ShapeVariant> = pieces
+                    .variants()
+                    .filter(|&v| !matches!(pieces[v].name, 'S' | 'Z' | 'O'))
+                    .collect();
+
+                match first.is_empty() {
+                    true => any(rng, pieces),
+                    false => first[rng.below(first.len())],
+                }
+            },
+            Randomizer::Tgm => {
+                let mut variant = any(rng, pieces);
+
+                for _ in 1..4 {
+                    if !history.contains(&variant) {
+                        break;
+                    }
+
+                    variant = any(rng, pieces);
+                }
+
+                variant
+            },
+        }
+    }
+}
@@ -0,0 +1,491 @@
+use std::collections::VecDeque;
+use std::time::SystemTime;
+use std::path::PathBuf;
+use std::path::Path;
+use std::fmt;
+use std::fs;
+
+use crate::message;
+use message::MinoDirection;
+use message::Rotation;
+use message::Held;
+
+use crate::error::Error;
+
+use super::cfg::Settings;
+use super::Tetris;
+use super::Input;
+
+// a replay file looks like this, one argument per `arg` line
+//
+//   seed 8046295169207419381
+//   arg --mode
+//   arg ultra
+//   12 keys 1
+//   12 left
+//   40 held cw -
+//   41 keys 1
+//   41 cw
+//   check 96 0 0 1600
+//   end 3600 1200 4 60000
+//
+// events are stamped with the number of frames simulated before them, and
+// the game plays out the same every time from the seed and the arguments.
+// checks are the frame, points, lines and milliseconds played after every
+// shape locked and when the game ended, to verify the replay against.
+
+/// where finished games are kept
+pub const DIR: &str = "replays";
+
+/// the longest game a replay is verified for, in seconds
+const LONGEST: u64 = 24 * 60 * 60;
+
+/// what the player did, at the frame it happened
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Event {
+    Input(Input),
+    /// the rotate and hold keys held down changed
+    Held(Held),
+    /// game keys pressed, not counting repeats
+    Keys(u32),
+}
+
+/// the score at some frame of the game
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Check {
+    pub tick: u64,
+    pub points: u32,
+    pub lines: u32,
+    /// play time in milliseconds
+    pub millis: u64,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Replay {
+    pub seed: u64,
+    /// the arguments the game was started with
+    pub args: Vec<String>,
+    /// events by the frame they happened on, in order
+    pub events: Vec<(u64, Event)>,
+    /// the score after every shape locked
+    pub checks: Vec<Check>,
+    /// the score when the game ended or was quit
+    pub end: Option<Check>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Verdict {
+    /// played out just as recorded, ending with this
+    Valid(Check),
+    /// the first check that doesn't match, `None` when either side has no check there
+    Diverged {
+        tick: u64,
+        recorded: Option<Check>,
+        simulated: Option<Check>,
+    },
+}
+
+impl fmt::Display for Check {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} points, {} lines and {:.3}s at frame {}",
+            self.points,
+            self.lines,
+            self.millis as f32 / 1000.0,
+            self.tick
+        )
+    }
+}
+
+impl Check {
+    fn to_text(self) -> String {
+        format!("{} {} {} {}", self.tick, self.points, self.lines, self.millis)
+    }
+
+    fn parse(values: &[&str]) -> Result<Self, Error> {
+        let invalid = |e| Error::Config(format!("Invalid check: {e}"));
+
+        match values {
+            [tick, points, lines, millis] => Ok(Self {
+                tick: tick.parse().map_err(invalid)?,
+                points: points.parse().map_err(invalid)?,
+                lines: lines.parse().map_err(invalid)?,
+                millis: millis.parse().map_err(invalid)?,
+            }),
+            _ => Err(Error::Config(format!(
+                "A check is a frame, points, lines and milliseconds, not `{}`.",
+                values.join(" ")
+            ))),
+        }
+    }
+}
+
+/// a replay being fed back into the game
+#[derive(Debug, Clone)]
+pub struct Playback {
+    /// events still to come, in order
+    pub events: VecDeque<(u64, Event)>,
+    pub paused: bool,
+    /// how many times faster than real time
+    pub speed: f32,
+}
+
+impl Playback {
+    pub const SPEEDS: [f32; 6] = [0.25, 0.5, 1.0, 2.0, 4.0, 8.0];
+
+    pub fn new(replay: Replay) -> Self {
+        Self {
+            events: replay.events.into(),
+            paused: false,
+            speed: 1.0,
+        }
+    }
+
+    /// the next speed up or down, staying at the fastest or slowest one
+    pub fn change_speed(&mut self, faster: bool) {
+        let current = Self::SPEEDS
+            .iter()
+            .position(|&s| s >= self.speed)
+            .unwrap_or(Self::SPEEDS.len() - 1);
+
+        let next = match faster {
+            true => (current + 1).min(Self::SPEEDS.len() - 1),
+            false => current.saturating_sub(1),
+        };
+
+        self.speed = Self::SPEEDS[next];
+    }
+}
+
+fn rotation_name(rot: Rotation) -> &'static str {
+    match rot {
+        Rotation::Clockwise => "cw",
+        Rotation::CounterClockwise => "ccw",
+        Rotation::Half => "half",
+    }
+}
+
+fn parse_rotation(name: &str) -> Result<Rotation, Error> {
+    match name {
+        "cw" => Ok(Rotation::Clockwise),
+        "ccw" => Ok(Rotation::CounterClockwise),
+        "half" => Ok(Rotation::Half),
+        _ => Err(Error::Config(format!("Unknown rotation `{name}`."))),
+    }
+}
+
+impl Event {
+    pub fn to_text(self) -> String {
+        match self {
+            Event::Input(Input::Turn(MinoDirection::Left)) => "left".into(),
+            Event::Input(Input::Turn(MinoDirection::Right)) => "right".into(),
+            Event::Input(Input::Rotate(rot)) => rotation_name(rot).into(),
+            Event::Input(Input::Hold) => "hold".into(),
+            Event::Held(held) => format!(
+                "held {} {}",
+                held.rotation.map(rotation_name).unwrap_or("-"),
+                match held.hold {
+                    true => "hold",
+                    false => "-",
+                },
+            ),
+            Event::Keys(presses) => format!("keys {presses}"),
+        }
+    }
+
+    pub fn parse(words: &[&str]) -> Result<Self, Error> {
+        match words {
+            ["left"] => Ok(Event::Input(Input::Turn(MinoDirection::Left))),
+            ["right"] => Ok(Event::Input(Input::Turn(MinoDirection::Right))),
+            ["hold"] => Ok(Event::Input(Input::Hold)),
+            [rot] => Ok(Event::Input(Input::Rotate(parse_rotation(rot)?))),
+            ["held", rot, hold] => Ok(Event::Held(Held {
+                rotation: match *rot {
+                    "-" => None,
+                    rot => Some(parse_rotation(rot)?),
+                },
+                hold: *hold == "hold",
+            })),
+            ["keys", presses] => presses
+                .parse()
+                .map(Event::Keys)
+                .map_err(|e| Error::Config(format!("Invalid keys: {e}"))),
+            _ => Err(Error::Config(format!("Unknown event `{}`.", words.join(" ")))),
+        }
+    }
+}
+
+impl Replay {
+    pub fn to_text(&self) -> String {
+        let mut text = format!("seed {}\n", self.seed);
+
+        for arg in self.args.iter() {
+            text += &format!("arg {arg}\n");
+        }
+
+        for (tick, event) in self.events.iter() {
+            text += &format!("{tick} {}\n", event.to_text());
+        }
+
+        for check in self.checks.iter() {
+            text += &format!("check {}\n", check.to_text());
+        }
+
+        if let Some(end) = self.end {
+            text += &format!("end {}\n", end.to_text());
+        }
+
+        text
+    }
+
+    pub fn parse(text: &str) -> Result<Self, Error> {
+        let mut replay = Self::default();
+
+        for line in text.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            // an argument can have spaces in it
+            if let Some(arg) = line.strip_prefix("arg ") {
+                replay.args.push(arg.to_string());
+                continue;
+            }
+
+            let words: Vec<&str> = line.split_whitespace().collect();
+
+            match words[..] {
+                ["seed", seed] => {
+                    replay.seed = seed
+                        .parse()
+                        .map_err(|e| Error::Config(format!("Invalid seed: {e}")))?
+                },
+                ["check", ..] => replay.checks.push(Check::parse(&words[1..])?),
+                ["end", ..] => replay.end = Some(Check::parse(&words[1..])?),
+                [tick, ..] => {
+                    let tick: u64 = tick
+                        .parse()
+                        .map_err(|e| Error::Config(format!("Invalid frame in `{line}`: {e}")))?;
+
+                    if replay
+                        .events
+                        .last()
+                        .map(|&(t, _)| t > tick)
+                        .unwrap_or(false)
+                    {
+                        return Err(Error::Config(format!(
+                            "`{line}` comes before the event above it."
+                        )));
+                    }
+
+                    replay.events.push((tick, Event::parse(&words[1..])?));
+                },
+                [] => (),
+            }
+        }
+
+        Ok(replay)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)
+            .map_err(Error::io(format!("Couldn't read replay {}", path.display())))?;
+
+        Self::parse(&text)
+            .map_err(|e| Error::Config(format!("Invalid replay {}: {e}", path.display())))
+    }
+
+    /// into a new file in `DIR` named after the mode and the time
+    pub fn save(&self, mode: &str) -> Result<PathBuf, Error> {
+        let secs = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let path = Path::new(DIR).join(format!("{mode}-{secs}.txt"));
+
+        fs::create_dir_all(DIR).map_err(Error::io(format!("Couldn't create {DIR}")))?;
+        self.write(&path)?;
+
+        Ok(path)
+    }
+
+    pub fn write(&self, path: impl AsRef<Path>) -> Result<(), Error> {
+        let path = path.as_ref();
+
+        fs::write(path, self.to_text())
+            .map_err(Error::io(format!("Couldn't save replay {}", path.display())))
+    }
+
+    /// play the game out again without a window, and compare every check with the recorded one
+    pub fn verify(&self) -> Result<Verdict, Error> {
+        let end = self
+            .end
+            .ok_or(Error::Config("The replay doesn't say how the game ended.".into()))?;
+        let settings = Settings::from_args(self.args.iter().cloned())?;
+        let frame = settings.rules.frames(1);
+
+        if end.tick > (LONGEST as f64 * settings.rules.fps) as u64 {
+            return Err(Error::Config(format!(
+                "The replay is longer than {}h, it isn't played out.",
+                LONGEST / 3600
+            )));
+        }
+
+        // every frame adds to the play time, only the clock running out stops it a frame short
+        let played = (frame * end.tick as u32).as_millis() as u64;
+
+        if end.millis > played || end.millis + frame.as_millis() as u64 + 1 < played {
+            return Ok(Verdict::Diverged {
+                tick: end.tick,
+                recorded: Some(end),
+                simulated: None,
+            });
+        }
+
+        let mut game = Tetris::playback(settings, self.clone());
+
+        while game.state.tick < end.tick && !game.is_over() {
+            game.step();
+        }
+
+        let count = self.checks.len().max(game.checks.len());
+
+        for i in 0..count {
+            let (recorded, simulated) = (self.checks.get(i).copied(), game.checks.get(i).copied());
+
+            if recorded != simulated {
+                let tick = [recorded, simulated]
+                    .iter()
+                    .flatten()
+                    .map(|c| c.tick)
+                    .min()
+                    .unwrap_or(0);
+
+                return Ok(Verdict::Diverged {
+                    tick,
+                    recorded,
+                    simulated,
+                });
+            }
+        }
+
+        let simulated = game.check();
+
+        Ok(match simulated == end {
+            true => Verdict::Valid(end),
+            false => Verdict::Diverged {
+                tick: end.tick.min(simulated.tick),
+                recorded: Some(end),
+                simulated: Some(simulated),
+            },
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// a few seconds of ultra with some moves in it, as recorded
+    fn recorded() -> Replay {
+        let args = ["--mode", "ultra"].map(String::from).to_vec();
+        let mut game = Tetris::with_seed(Settings::from_args(args.into_iter()).unwrap(), 42);
+
+        for frame in 0..600 {
+            match frame % 90 {
+                10 => game.input(Input::Turn(MinoDirection::Left)),
+                20 => game.input(Input::Rotate(Rotation::Clockwise)),
+                30 => game.input(Input::Hold),
+                _ => (),
+            }
+
+            game.step();
+        }
+
+        game.replay()
+    }
+
+    #[test]
+    fn text_round_trip() {
+        let replay = recorded();
+
+        assert!(!replay.events.is_empty());
+        assert!(!replay.checks.is_empty());
+        assert_eq!(Replay::parse(&replay.to_text()).unwrap(), replay);
+    }
+
+    #[test]
+    fn rejects_overflowing_numbers() {
+        assert!(Replay::parse("end 60 4294967296 0 1000").is_err());
+        assert!(Replay::parse("check 60 0 4294967296 1000").is_err());
+        assert!(Replay::parse("end 18446744073709551616 0 0 1000").is_err());
+    }
+
+    #[test]
+    fn verifies_recorded_game() {
+        let replay = recorded();
+
+        assert_eq!(replay.verify().unwrap(), Verdict::Valid(replay.end.unwrap()));
+    }
+
+    #[test]
+    fn catches_tampered_score() {
+        let mut replay = recorded();
+        replay.end.as_mut().unwrap().points += 100;
+
+        assert!(matches!(replay.verify().unwrap(), Verdict::Diverged { .. }));
+
+        let mut replay = recorded();
+        replay.checks[0].lines += 1;
+
+        assert!(matches!(replay.verify().unwrap(), Verdict::Diverged { .. }));
+    }
+
+    fn step_until_locked(game: &mut Tetris, pieces: usize) {
+        while game.checks.len() < pieces {
+            game.step();
+        }
+    }
+
+    #[test]
+    fn verifies_undone_practice() {
+        let args = ["--mode", "practice"].map(String::from).to_vec();
+        let mut game = Tetris::with_seed(Settings::from_args(args.into_iter()).unwrap(), 7);
+        step_until_locked(&mut game, 1);
+
+        // the keys held on the path taken back don't stay held
+        game.hold_keys(Held {
+            rotation: Some(Rotation::Clockwise),
+            hold: true,
+        });
+        step_until_locked(&mut game, 2);
+        game.undo();
+        step_until_locked(&mut game, 4);
+
+        let replay = game.replay();
+
+        assert_eq!(replay.verify().unwrap(), Verdict::Valid(replay.end.unwrap()));
+    }
+
+    #[test]
+    fn refuses_endless_games() {
+        let mut replay = recorded();
+        replay.end.as_mut().unwrap().tick = u64::MAX;
+
+        assert!(replay.verify().is_err());
+
+        // a longer game than the time says isn't played out
+        let mut replay = recorded();
+        replay.end.as_mut().unwrap().tick = 50_000_000 / 60;
+
+        assert!(matches!(
+            replay.verify().unwrap(),
+            Verdict::Diverged {
+                simulated: None,
+                ..
+            }
+        ));
+    }
+}
@@ -0,0 +1,70 @@
+// xoshiro256** by blackman and vigna, seeded with splitmix64 like their
+// reference code. numbers are drawn from it by `below` and `chance` rather than
+// rand's sampling, so a seed deals the same shapes on every platform and with
+// every version of rand, and its state is four numbers.
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Xoshiro256 {
+    /// everything needed to carry on where it left off
+    pub state: [u64; 4],
+}
+
+impl Xoshiro256 {
+    pub fn new(seed: u64) -> Self {
+        let mut seed = seed;
+        let mut state = [0; 4];
+
+        for s in state.iter_mut() {
+            seed = seed.wrapping_add(0x9e3779b97f4a7c15);
+
+            let mut z = seed;
+            z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+            *s = z ^ (z >> 31);
+        }
+
+        Self { state }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        let s = &mut self.state;
+        let result = s[1].wrapping_mul(5).rotate_left(7).wrapping_mul(9);
+        let t = s[1] << 17;
+
+        s[2] ^= s[0];
+        s[3] ^= s[1];
+        s[1] ^= s[2];
+        s[0] ^= s[3];
+        s[2] ^= t;
+        s[3] = s[3].rotate_left(45);
+
+        result
+    }
+
+    /// any number from 0 up to but not including `n`, each as likely, 0 when `n` is 0
+    pub fn below(&mut self, n: usize) -> usize {
+        let n = n as u64;
+
+        if n <= 1 {
+            return 0;
+        }
+
+        // 2^64 % n numbers at the bottom would make the first few remainders more likely
+        let threshold = n.wrapping_neg() % n;
+
+        loop {
+            let x = self.next_u64();
+
+            if x >= threshold {
+                return (x % n) as usize;
+            }
+        }
+    }
+
+    /// true with a probability of `p`, from 0.0 for never to 1.0 for always
+    pub fn chance(&mut self, p: f64) -> bool {
+        // the upper 53 bits, as many as fit in the fraction of an f64
+        let x = (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64;
+        x < p
+    }
+}
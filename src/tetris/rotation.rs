@@ -0,0 +1,175 @@
+use crate::message;
+use message::Rotation;
+
+use crate::error::Error;
+
+use super::pieces::Piece;
+
+/// how a shape turns and where it goes when turning in place is blocked
+pub trait RotationSystem {
+    /// the box origin and orientation after turning `rot` from `orientation` with the box
+    /// at `origin`, `None` when the shape can't turn
+    fn rotate(
+        &self,
+        piece: &Piece,
+        orientation: usize,
+        origin: [i32; 2],
+        rot: Rotation,
+        is_free: &dyn Fn([i32; 2]) -> bool,
+    ) -> Option<([i32; 2], usize)>;
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum RotationKind {
+    /// the super rotation system, or whatever kick tables the piece set has
+    Srs,
+    /// arika's rotation system, as in tgm
+    Ars,
+    /// nintendo's rotation system, as on the nes
+    Nrs,
+    /// turn in place or not at all
+    NoKick,
+}
+
+impl RotationKind {
+    pub fn from_name(name: &str) -> Result<Self, Error> {
+        match name {
+            "srs" => Ok(RotationKind::Srs),
+            "ars" => Ok(RotationKind::Ars),
+            "nrs" => Ok(RotationKind::Nrs),
+            "none" => Ok(RotationKind::NoKick),
+            _ => Err(Error::Config(format!("Unknown rotation system `{name}`."))),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            RotationKind::Srs => "srs",
+            RotationKind::Ars => "ars",
+            RotationKind::Nrs => "nrs",
+            RotationKind::NoKick => "none",
+        }
+    }
+
+    pub fn system(&self) -> &'static dyn RotationSystem {
+        match self {
+            RotationKind::Srs => &Srs,
+            RotationKind::Ars => &Ars,
+            RotationKind::Nrs => &Nrs,
+            RotationKind::NoKick => &NoKick,
+        }
+    }
+
+    /// built in piece set with the layouts of the system, unless another one is picked
+    pub fn pieces(&self) -> &'static str {
+        match self {
+            RotationKind::Srs | RotationKind::NoKick => "tetrominoes",
+            RotationKind::Ars => "ars",
+            RotationKind::Nrs => "nrs",
+        }
+    }
+}
+
+fn turned(orientation: usize, rot: Rotation) -> usize {
+    match rot {
+        Rotation::Clockwise => (orientation + 1) % 4,
+        Rotation::CounterClockwise => (orientation + 3) % 4,
+        Rotation::Half => (orientation + 2) % 4,
+    }
+}
+
+// the first of `kicks` that leaves room for the turned shape
+fn kick(
+    piece: &Piece,
+    orientation: usize,
+    [x, y]: [i32; 2],
+    kicks: &[[i32; 2]],
+    is_free: &dyn Fn([i32; 2]) -> bool,
+) -> Option<([i32; 2], usize)> {
+    kicks
+        .iter()
+        .map(|&[dc, dr]| [x + dc, y + dr])
+        .find(|&origin| piece.cells(orientation, origin).all(is_free))
+        .map(|origin| (origin, orientation))
+}
+
+pub struct Srs;
+
+impl RotationSystem for Srs {
+    fn rotate(
+        &self,
+        piece: &Piece,
+        orientation: usize,
+        origin: [i32; 2],
+        rot: Rotation,
+        is_free: &dyn Fn([i32; 2]) -> bool,
+    ) -> Option<([i32; 2], usize)> {
+        let to = turned(orientation, rot);
+        kick(piece, to, origin, piece.kicks(orientation, to), is_free)
+    }
+}
+
+// layouts and kicks come from `pieces/ars.txt`, what a table can't say is
+// that l, j and t only kick when they aren't blocked in the center column
+// of their box first
+pub struct Ars;
+
+impl RotationSystem for Ars {
+    fn rotate(
+        &self,
+        piece: &Piece,
+        orientation: usize,
+        origin: [i32; 2],
+        rot: Rotation,
+        is_free: &dyn Fn([i32; 2]) -> bool,
+    ) -> Option<([i32; 2], usize)> {
+        let to = turned(orientation, rot);
+        let kicks = piece.kicks(orientation, to);
+
+        if !matches!(piece.name, 'L' | 'J' | 'T') {
+            return kick(piece, to, origin, kicks, is_free);
+        }
+
+        // the first blocked cell of the turned shape, row by row
+        let [x, y] = origin;
+        let mut cells = piece.layouts[to].clone();
+        cells.sort_by_key(|&[c, r]| (r, c));
+
+        match cells.iter().find(|&&[c, r]| !is_free([x + c, y + r])) {
+            Some([1, _]) => None,
+            _ => kick(piece, to, origin, kicks, is_free),
+        }
+    }
+}
+
+// the nes turns around a fixed center, that's all in `pieces/nrs.txt`
+pub struct Nrs;
+
+impl RotationSystem for Nrs {
+    fn rotate(
+        &self,
+        piece: &Piece,
+        orientation: usize,
+        origin: [i32; 2],
+        rot: Rotation,
+        is_free: &dyn Fn([i32; 2]) -> bool,
+    ) -> Option<([i32; 2], usize)> {
+        kick(piece, turned(orientation, rot), origin, &[[0, 0]], is_free)
+    }
+}
+
+/// ignores the kick tables of any piece set
+pub struct NoKick;
+
+impl RotationSystem for NoKick {
+    fn rotate(
+        &self,
+        piece: &Piece,
+        orientation: usize,
+        origin: [i32; 2],
+        rot: Rotation,
+        is_free: &dyn Fn([i32; 2]) -> bool,
+    ) -> Option<([i32; 2], usize)> {
+        kick(piece, turned(orientation, rot), origin, &[[0, 0]], is_free)
+    }
+}
@@ -0,0 +1,323 @@
+use std::time::Duration;
+
+use crate::error::Error;
+
+use super::randomizer::Randomizer;
+use super::score::LineClear;
+use super::score::SpinKind;
+use super::score::Score;
+use super::master;
+use super::rotation::RotationKind;
+
+/// frames per row on the nes, from level 0 up to level 29 and beyond
+const NES_GRAVITY: [u32; 30] = [
+    48, 43, 38, 33, 28, 23, 18, 13, 8, 6, // 0-9
+    5, 5, 5, 4, 4, 4, 3, 3, 3, 2, // 10-19
+    2, 2, 2, 2, 2, 2, 2, 2, 2, 1, // 20-29
+];
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Scoring {
+    /// 100/300/500/800 times the level, t-spins earn more
+    Guideline,
+    /// 40/100/300/1200 times the level plus one
+    Nes,
+    /// based on the level and the combo, levels go up with every piece
+    Tgm,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Gravity {
+    /// the same number of frames per row on every level
+    Fixed(u32),
+    /// frames per row looked up by level
+    Nes,
+    /// 1/256 rows per frame looked up by level, up to 20G
+    Tgm,
+}
+
+/// which shapes can spin, spinning is turning into a spot that can't be moved into
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SpinRule {
+    None,
+    /// t-spins with three corners around the center of the t taken
+    TSpins,
+    /// t-spins, and minis for other shapes that can't move left, right or up after turning
+    AllMini,
+    /// like `AllMini`, but the other shapes make full spins
+    All,
+}
+
+impl SpinRule {
+    pub fn from_name(name: &str) -> Result<Self, Error> {
+        match name {
+            "none" => Ok(SpinRule::None),
+            "t" => Ok(SpinRule::TSpins),
+            "mini" => Ok(SpinRule::AllMini),
+            "all" => Ok(SpinRule::All),
+            _ => Err(Error::Config(format!("Unknown spin rule `{name}`."))),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            SpinRule::None => "none",
+            SpinRule::TSpins => "t",
+            SpinRule::AllMini => "mini",
+            SpinRule::All => "all",
+        }
+    }
+}
+
+/// how far the focused shape falls
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Fall {
+    /// one row every this many frames
+    Every(u32),
+    /// this many 1/256 rows every frame
+    Per256(u32),
+}
+
+/// delays in frames, which may change as the level goes up
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Timings {
+    /// entry delay before the next shape appears
+    pub are: u32,
+    /// entry delay after a line clear
+    pub line_are: u32,
+    /// frames the cleared rows stay on the board
+    pub line_clear: u32,
+    /// frames a direction is held before it repeats
+    pub das: u32,
+    /// frames a grounded shape waits before locking, `None` locks it on the next row of gravity
+    pub lock: Option<u32>,
+}
+
+/// how a game plays, independent of its mode
+#[derive(Debug, Clone)]
+pub struct Rules {
+    pub name: &'static str,
+    /// frames per second the timings below are counted in
+    pub fps: f64,
+    pub gravity: Gravity,
+    pub scoring: Scoring,
+    pub spins: SpinRule,
+    pub rotation: RotationKind,
+    /// timings from the level each one starts at, in ascending order
+    pub timings: &'static [(u32, Timings)],
+    /// frames between repeated moves
+    pub das_repeat: u32,
+    pub hold: bool,
+    /// initial rotation, a rotate key held while the next shape spawns turns it right away
+    pub irs: bool,
+    /// initial hold, the hold key held while the next shape spawns swaps it right away
+    pub ihs: bool,
+    /// number of upcoming shapes shown
+    pub previews: usize,
+    pub randomizer: Randomizer,
+    pub start_level: u32,
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Self::standard()
+    }
+}
+
+impl Rules {
+    pub fn standard() -> Self {
+        const TIMINGS: Timings = Timings {
+            are: 6,
+            line_are: 6,
+            line_clear: 20,
+            das: 12,
+            lock: None,
+        };
+
+        Self {
+            name: "standard",
+            fps: 60.0,
+            gravity: Gravity::Fixed(12),
+            scoring: Scoring::Guideline,
+            spins: SpinRule::AllMini,
+            rotation: RotationKind::Srs,
+            timings: &[(0, TIMINGS)],
+            das_repeat: 12,
+            hold: true,
+            irs: true,
+            ihs: true,
+            previews: 3,
+            randomizer: Randomizer::Uniform,
+            start_level: 1,
+        }
+    }
+
+    pub fn nes() -> Self {
+        const TIMINGS: Timings = Timings {
+            are: 10,
+            line_are: 10,
+            line_clear: 18,
+            das: 16,
+            lock: None,
+        };
+
+        Self {
+            name: "nes",
+            fps: 60.0988,
+            gravity: Gravity::Nes,
+            scoring: Scoring::Nes,
+            spins: SpinRule::None,
+            rotation: RotationKind::Nrs,
+            timings: &[(0, TIMINGS)],
+            das_repeat: 6,
+            hold: false,
+            irs: false,
+            ihs: false,
+            previews: 1,
+            randomizer: Randomizer::Nes,
+            start_level: 0,
+        }
+    }
+
+    pub fn tgm() -> Self {
+        Self {
+            name: "tgm",
+            fps: 60.0,
+            gravity: Gravity::Tgm,
+            scoring: Scoring::Tgm,
+            spins: SpinRule::None,
+            rotation: RotationKind::Ars,
+            timings: &master::TIMINGS,
+            das_repeat: 1,
+            hold: false,
+            irs: true,
+            ihs: false,
+            previews: 1,
+            randomizer: Randomizer::Tgm,
+            start_level: 0,
+        }
+    }
+
+    pub fn from_name(name: &str) -> Result<Self, Error> {
+        match name {
+            "standard" => Ok(Self::standard()),
+            "nes" => Ok(Self::nes()),
+            "tgm" => Ok(Self::tgm()),
+            _ => Err(Error::Config(format!("Unknown rules `{name}`."))),
+        }
+    }
+
+    pub fn frames(&self, frames: u32) -> Duration {
+        Duration::from_secs_f64(frames as f64 / self.fps)
+    }
+
+    pub fn timings(&self, level: u32) -> Timings {
+        self.timings
+            .iter()
+            .rev()
+            .find(|&&(from, _)| level >= from)
+            .map(|&(_, timings)| timings)
+            .unwrap_or(self.timings[0].1)
+    }
+
+    pub fn fall(&self, level: u32) -> Fall {
+        match self.gravity {
+            Gravity::Fixed(frames) => Fall::Every(frames),
+            Gravity::Nes => Fall::Every(NES_GRAVITY[(level as usize).min(NES_GRAVITY.len() - 1)]),
+            Gravity::Tgm => Fall::Per256(master::gravity(level)),
+        }
+    }
+
+    /// level reached after clearing this many lines, unused by tgm scoring
+    pub fn level(&self, lines: u32) -> u32 {
+        let start = self.start_level;
+
+        match self.scoring {
+            Scoring::Guideline | Scoring::Tgm => start + lines / 10,
+            // starting above level 0 delays the first level up
+            Scoring::Nes => {
+                let first = (start * 10 + 10).min((start * 10).saturating_sub(50).max(100));
+
+                match lines.checked_sub(first) {
+                    Some(past) => start + 1 + past / 10,
+                    None => start,
+                }
+            },
+        }
+    }
+
+    /// points for a clear, with the score as it was before it
+    pub fn points(&self, clear: LineClear, score: &Score) -> u32 {
+        // a pentris is worth half again a tetris, longer clears count as one
+        const LINE_POINTS: [u32; 5] = [100, 300, 500, 800, 1200];
+        const SPIN_POINTS: [u32; 3] = [800, 1200, 1600];
+        const MINI_POINTS: [u32; 2] = [200, 400];
+        const NES_POINTS: [u32; 5] = [40, 100, 300, 1200, 1800];
+        const PERFECT_CLEAR_POINTS: [u32; 5] = [800, 1200, 1800, 2000, 3000];
+
+        let (lines, level) = (clear.lines as u32, score.level);
+        let index = clear.lines.clamp(1, LINE_POINTS.len()) - 1;
+
+        let bonus = match (self.scoring, clear.perfect_clear) {
+            (Scoring::Guideline, true) => PERFECT_CLEAR_POINTS[index].saturating_mul(level),
+            _ => 0,
+        };
+
+        // tgm calls it a bravo and quadruples the points
+        let bravo = match clear.perfect_clear {
+            true => 4,
+            false => 1,
+        };
+
+        // the level keeps going up in a long enough game, the score stops at the top instead
+        let points = match (self.scoring, clear.spin.map(|s| s.kind)) {
+            (Scoring::Guideline, Some(SpinKind::Full)) if lines <= 3 => SPIN_POINTS[index],
+            (Scoring::Guideline, Some(SpinKind::Mini)) if lines <= 2 => MINI_POINTS[index],
+            (Scoring::Guideline, _) => LINE_POINTS[index],
+            (Scoring::Nes, _) => NES_POINTS[index],
+            (Scoring::Tgm, _) => (level.saturating_add(lines).div_ceil(4))
+                .saturating_mul(lines)
+                .saturating_mul(score.multiplier)
+                .saturating_mul(bravo),
+        };
+
+        let factor = match self.scoring {
+            Scoring::Guideline => level,
+            Scoring::Nes => level.saturating_add(1),
+            Scoring::Tgm => 1,
+        };
+
+        points.saturating_mul(factor).saturating_add(bonus)
+    }
+
+    /// lines of garbage a clear sends, with the score as it was before it
+    pub fn attack(&self, clear: LineClear, score: &Score) -> u32 {
+        const LINE_ATTACK: [u32; 5] = [0, 1, 2, 4, 6];
+        const SPIN_ATTACK: [u32; 3] = [2, 4, 6];
+        const MINI_ATTACK: [u32; 2] = [0, 1];
+        // by the number of clears in a row before this one
+        const COMBO_ATTACK: [u32; 12] = [0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 4, 5];
+
+        let index = clear.lines.clamp(1, LINE_ATTACK.len()) - 1;
+
+        let lines = match clear.spin.map(|s| s.kind) {
+            Some(SpinKind::Full) if clear.lines <= 3 => SPIN_ATTACK[index],
+            Some(SpinKind::Mini) if clear.lines <= 2 => MINI_ATTACK[index],
+            _ => LINE_ATTACK[index],
+        };
+
+        let back_to_back = match clear.is_difficult() && score.back_to_back > 0 {
+            true => 1,
+            false => 0,
+        };
+
+        let combo = COMBO_ATTACK[(score.combo as usize).min(COMBO_ATTACK.len() - 1)];
+
+        let perfect_clear = match clear.perfect_clear {
+            true => 10,
+            false => 0,
+        };
+
+        lines + back_to_back + combo + perfect_clear
+    }
+}
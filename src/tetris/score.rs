@@ -0,0 +1,185 @@
+use std::fmt;
+
+use super::rules::Scoring;
+use super::rules::Rules;
+use super::master;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SpinKind {
+    Full,
+    Mini,
+}
+
+/// a shape turned into a spot it couldn't have moved into
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Spin {
+    /// name of the piece that spun
+    pub piece: char,
+    pub kind: SpinKind,
+}
+
+/// rows cleared by a single locked shape
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct LineClear {
+    pub lines: usize,
+    pub spin: Option<Spin>,
+    /// nothing is left on the board once the rows are gone
+    pub perfect_clear: bool,
+}
+
+impl LineClear {
+    /// clears that keep a back to back chain going, tetrises and spins
+    pub fn is_difficult(&self) -> bool {
+        self.lines >= 4 || self.spin.is_some()
+    }
+
+    /// e.g `tetris` or `t-spin mini double`
+    pub fn name(&self) -> String {
+        const NAMES: [&str; 5] = ["single", "double", "triple", "tetris", "pentris"];
+
+        let lines = NAMES[self.lines.clamp(1, NAMES.len()) - 1];
+
+        match self.spin {
+            Some(spin) => {
+                let mini = match spin.kind {
+                    SpinKind::Full => "",
+                    SpinKind::Mini => " mini",
+                };
+
+                format!("{}-spin{mini} {lines}", spin.piece.to_ascii_lowercase())
+            },
+            None => lines.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct Score {
+    pub points: u32,
+    pub lines: u32,
+    pub level: u32,
+    /// number of singles, doubles, triples, tetrises and pentrises,
+    /// clears of more lines from a piece set file count as pentrises
+    pub clears: [u32; 5],
+    /// points earned by each kind of clear, same order as `clears`
+    pub clear_points: [u32; 5],
+    /// cleared rows that had garbage in them, also counted in `lines`
+    pub garbage_lines: u32,
+    /// full spins that cleared at least a line
+    pub spins: u32,
+    /// mini spins that cleared at least a line
+    pub mini_spins: u32,
+    pub perfect_clears: u32,
+    /// lines of garbage the clears would send to an opponent
+    pub attack: u32,
+    /// clears in a row, back to 0 when a shape clears nothing
+    pub combo: u32,
+    /// difficult clears in a row, back to 0 after any other clear
+    pub back_to_back: u32,
+    /// combo multiplier of tgm scoring, back to 1 when a shape clears nothing
+    pub multiplier: u32,
+}
+
+impl Score {
+    pub fn new(rules: &Rules) -> Self {
+        Self {
+            level: rules.start_level,
+            multiplier: 1,
+            ..Default::default()
+        }
+    }
+
+    pub fn break_combo(&mut self) {
+        self.multiplier = 1;
+        self.combo = 0;
+    }
+
+    /// tgm levels go up with every new shape, except at the end of a section
+    pub fn piece_spawned(&mut self, rules: &Rules) {
+        if rules.scoring == Scoring::Tgm && !master::is_section_stop(self.level) {
+            self.level += 1;
+        }
+    }
+
+    pub fn award(&mut self, clear: LineClear, rules: &Rules) {
+        let lines = clear.lines;
+
+        if lines == 0 {
+            return;
+        }
+
+        if rules.scoring == Scoring::Tgm {
+            self.multiplier += 2 * lines as u32 - 2;
+        }
+
+        let points = rules.points(clear, self);
+        self.attack += rules.attack(clear, self);
+
+        if clear.perfect_clear {
+            self.perfect_clears += 1;
+        }
+
+        match clear.spin.map(|s| s.kind) {
+            Some(SpinKind::Full) => self.spins += 1,
+            Some(SpinKind::Mini) => self.mini_spins += 1,
+            None => (),
+        }
+
+        self.combo += 1;
+        self.back_to_back = match clear.is_difficult() {
+            true => self.back_to_back + 1,
+            false => 0,
+        };
+
+        self.points = self.points.saturating_add(points);
+        self.lines += lines as u32;
+        self.level = match rules.scoring {
+            Scoring::Tgm => (self.level + lines as u32).min(master::MAX_LEVEL),
+            _ => rules.level(self.lines),
+        };
+        let index = lines.min(self.clears.len()) - 1;
+
+        self.clears[index] += 1;
+        self.clear_points[index] = self.clear_points[index].saturating_add(points);
+    }
+}
+
+impl fmt::Display for Score {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let names = ["single", "double", "triple", "tetris", "pentris"];
+
+        for (i, name) in names.iter().enumerate() {
+            // only pentominoes get that far
+            if i == 4 && self.clears[i] == 0 {
+                continue;
+            }
+
+            writeln!(f, "{name:>8}: {:>4} x = {:>7}", self.clears[i], self.clear_points[i])?;
+        }
+
+        writeln!(f, "   lines: {:>4}", self.lines)?;
+
+        if self.spins > 0 {
+            writeln!(f, "   spins: {:>4}", self.spins)?;
+        }
+
+        if self.mini_spins > 0 {
+            writeln!(f, "   minis: {:>4}", self.mini_spins)?;
+        }
+
+        if self.perfect_clears > 0 {
+            writeln!(f, " perfect: {:>4}", self.perfect_clears)?;
+        }
+
+        if self.attack > 0 {
+            writeln!(f, "  attack: {:>4}", self.attack)?;
+        }
+
+        if self.garbage_lines > 0 {
+            writeln!(f, " garbage: {:>4}", self.garbage_lines)?;
+        }
+
+        writeln!(f, "   level: {:>4}", self.level)?;
+        write!(f, "   score: {:>14}", self.points)
+    }
+}
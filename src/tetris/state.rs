@@ -0,0 +1,707 @@
+use std::collections::VecDeque;
+use std::iter::Peekable;
+use std::str::SplitWhitespace;
+use std::time::Duration;
+use std::str::FromStr;
+use std::fmt::Display;
+
+use crate::message::Held;
+use crate::error::Error;
+
+use super::garbage::Garbage;
+use super::master::Master;
+use super::master;
+use super::finesse::Target;
+use super::finesse::Drill;
+use super::score::LineClear;
+use super::score::SpinKind;
+use super::score::Score;
+use super::score::Spin;
+use super::stats::Stats;
+use super::mino::ShapeVariant;
+use super::mino::Shape;
+use super::mino::Mino;
+use super::mode::Outcome;
+use super::mode::Mode;
+use super::rng::Xoshiro256;
+use super::cfg::Settings;
+use super::replay::Event;
+use super::Phase;
+use super::Input;
+
+// a game quit halfway is saved as its replay so far, followed by the state it was
+// left in, one field a line with `-` for nothing
+//
+//   state
+//   mino 4 19 true false 20100000000 0 255 255
+//   focused 2 1 3 0
+//   elapsed 20566666660
+//   tick 1234
+//   phase locking 3
+//   queue 0 5 3 1 6
+//   hold -
+//   ...
+//   rng 8046295169207419381 1 2 3
+//
+// durations are in nanoseconds and shapes are by their position in the piece set, so
+// the game goes on exactly where it was left off rather than being played again
+
+/// the game quit halfway, kept to be resumed
+pub const SAVE: &str = "save.txt";
+
+/// everything that plays out from the seed and the events, and nothing else. restoring it
+/// puts the game back exactly where it was
+#[derive(Debug, Clone)]
+pub struct State {
+    pub minos: [[Option<Mino>; 10]; 20],
+    pub focused_shape: Option<Shape>,
+    /// time spent playing, frozen once the game is over
+    pub elapsed: Duration,
+    /// frames simulated so far
+    pub tick: u64,
+    pub phase: Phase,
+    /// rows waiting to be removed during `Phase::LineClear`
+    pub clearing: Vec<usize>,
+    /// progress towards the next row of gravity
+    pub fall: u32,
+    /// moves made while there was no shape to move, replayed when the next one spawns
+    pub buffered: Vec<Input>,
+    /// rotate and hold keys held down, for initial rotation and hold
+    pub held: Held,
+    pub outcome: Option<Outcome>,
+    pub score: Score,
+    pub stats: Stats,
+    pub garbage: Option<Garbage>,
+    pub master: Option<Master>,
+    pub drill: Option<Drill>,
+    /// fixed pieces to play instead of random ones
+    pub sequence: Option<VecDeque<ShapeVariant>>,
+    /// random pieces rolled ahead of time for the preview
+    pub queue: VecDeque<ShapeVariant>,
+    /// the last shapes rolled, the most recent one at the back
+    pub history: VecDeque<ShapeVariant>,
+    pub hold: Option<ShapeVariant>,
+    /// the focused shape hasn't been swapped with the hold yet
+    pub can_hold: bool,
+    pub last_clear: Option<LineClear>,
+    /// play time of the last perfect clear, for its callout
+    pub perfect_clear_at: Option<Duration>,
+    /// keys pressed since the last shape locked
+    pub piece_keys: u32,
+    /// play time of the last finesse fault, for its callout
+    pub fault_at: Option<Duration>,
+    /// the focused shape was rotated after its last move
+    pub last_rotated: bool,
+    /// how far the last rotation was kicked away from turning in place
+    pub last_kick: [i32; 2],
+    /// the spin the last locked shape made
+    pub spin: Option<Spin>,
+    pub rng: Xoshiro256,
+}
+
+impl State {
+    /// the start of a game, before the first frame
+    pub fn new(cfg: &Settings, seed: u64) -> Self {
+        let garbage = match cfg.mode {
+            Mode::Dig { lines, messiness } => Some(Garbage::new(lines, messiness)),
+            _ => None,
+        };
+
+        let master = match cfg.mode {
+            Mode::Master => Some(Master::default()),
+            _ => None,
+        };
+
+        let drill = match cfg.mode {
+            Mode::Drill => Some(Drill::default()),
+            _ => None,
+        };
+
+        let (minos, sequence) = match &cfg.mode {
+            Mode::Puzzle(puzzle) => {
+                let pieces = puzzle.pieces.iter().filter_map(|&c| cfg.pieces.find(c));
+                (puzzle.board, Some(pieces.collect()))
+            },
+            _ => ([[None; 10]; 20], None),
+        };
+
+        Self {
+            minos,
+            focused_shape: None,
+            elapsed: Duration::ZERO,
+            tick: 0,
+            phase: Phase::Entry(cfg.timings(cfg.rules.start_level).are),
+            clearing: Vec::new(),
+            fall: 0,
+            buffered: Vec::new(),
+            held: Held::default(),
+            outcome: None,
+            score: Score::new(&cfg.rules),
+            stats: Stats::new(&cfg.pieces),
+            garbage,
+            master,
+            drill,
+            sequence,
+            queue: VecDeque::new(),
+            history: VecDeque::new(),
+            hold: None,
+            can_hold: true,
+            last_clear: None,
+            perfect_clear_at: None,
+            piece_keys: 0,
+            fault_at: None,
+            last_rotated: false,
+            last_kick: [0, 0],
+            spin: None,
+            rng: Xoshiro256::new(seed),
+        }
+    }
+}
+
+// the values of a line, read in the order they were written
+struct Values<'a> {
+    line: &'a str,
+    words: Peekable<SplitWhitespace<'a>>,
+}
+
+impl<'a> Values<'a> {
+    fn new(line: &'a str) -> Self {
+        Self {
+            line,
+            words: line.split_whitespace().peekable(),
+        }
+    }
+
+    fn word(&mut self) -> Result<&'a str, Error> {
+        self.words
+            .next()
+            .ok_or(Error::Config(format!("`{}` is missing a value.", self.line)))
+    }
+
+    fn value<T: FromStr>(&mut self) -> Result<T, Error>
+    where
+        T::Err: Display,
+    {
+        let word = self.word()?;
+
+        word.parse()
+            .map_err(|e| Error::Config(format!("Invalid value `{word}` in `{}`: {e}", self.line)))
+    }
+
+    fn duration(&mut self) -> Result<Duration, Error> {
+        self.value().map(Duration::from_nanos)
+    }
+
+    /// `None` for `-`
+    fn optional<T>(
+        &mut self,
+        read: impl FnOnce(&mut Self) -> Result<T, Error>,
+    ) -> Result<Option<T>, Error> {
+        match self.words.next_if_eq(&"-") {
+            Some(_) => Ok(None),
+            None => read(self).map(Some),
+        }
+    }
+
+    /// every value left, read the same way
+    fn rest<T>(&mut self, read: impl Fn(&mut Self) -> Result<T, Error>) -> Result<Vec<T>, Error> {
+        let mut values = Vec::new();
+
+        while self.words.peek().is_some() {
+            values.push(read(self)?);
+        }
+
+        Ok(values)
+    }
+
+    fn variant(&mut self, cfg: &Settings) -> Result<ShapeVariant, Error> {
+        let i = self.value()?;
+
+        match i < cfg.pieces.pieces.len() {
+            true => Ok(ShapeVariant(i)),
+            false => Err(Error::Config(format!(
+                "There's no piece {i} in the set, in `{}`.",
+                self.line
+            ))),
+        }
+    }
+
+    fn spin(&mut self) -> Result<Spin, Error> {
+        let piece = self.value()?;
+        let kind = match self.word()? {
+            "full" => SpinKind::Full,
+            "mini" => SpinKind::Mini,
+            kind => return Err(Error::Config(format!("Unknown spin `{kind}`."))),
+        };
+
+        Ok(Spin { piece, kind })
+    }
+}
+
+fn nanos(time: Duration) -> String {
+    time.as_nanos().to_string()
+}
+
+fn optional<T>(value: Option<T>, write: impl FnOnce(T) -> String) -> String {
+    value.map(write).unwrap_or("-".into())
+}
+
+fn spin_text(spin: Spin) -> String {
+    let kind = match spin.kind {
+        SpinKind::Full => "full",
+        SpinKind::Mini => "mini",
+    };
+
+    format!("{} {kind}", spin.piece)
+}
+
+fn variants<'a>(variants: impl IntoIterator<Item = &'a ShapeVariant>) -> String {
+    let variants: Vec<String> = variants.into_iter().map(|v| v.0.to_string()).collect();
+    variants.join(" ")
+}
+
+impl State {
+    pub fn to_text(&self) -> String {
+        let mut lines = Vec::new();
+
+        for (row, minos) in self.minos.iter().enumerate() {
+            for (column, mino) in minos.iter().enumerate() {
+                if let Some(m) = mino {
+                    let [r, g, b] = m.color;
+
+                    lines.push(format!(
+                        "mino {column} {row} {} {} {} {r} {g} {b}",
+                        m.locked,
+                        m.garbage,
+                        nanos(m.locked_at)
+                    ));
+                }
+            }
+        }
+
+        let focused = optional(self.focused_shape.as_ref(), |m| {
+            format!("{} {} {} {}", m.variant.0, m.orientation, m.origin[0], m.origin[1])
+        });
+        let phase = match self.phase {
+            Phase::Falling => "falling".to_string(),
+            Phase::Locking(frames) => format!("locking {frames}"),
+            Phase::LineClear(frames) => format!("clearing {frames}"),
+            Phase::Entry(frames) => format!("entry {frames}"),
+        };
+        let clearing: Vec<String> = self.clearing.iter().map(|r| r.to_string()).collect();
+        let buffered: Vec<String> = self
+            .buffered
+            .iter()
+            .map(|&input| Event::Input(input).to_text())
+            .collect();
+
+        lines.extend([
+            format!("focused {focused}"),
+            format!("elapsed {}", nanos(self.elapsed)),
+            format!("tick {}", self.tick),
+            format!("phase {phase}"),
+            format!("clearing {}", clearing.join(" ")),
+            format!("fall {}", self.fall),
+            format!("buffered {}", buffered.join(" ")),
+            Event::Held(self.held).to_text(),
+            format!("outcome {}", optional(self.outcome, |o| o.name().to_string())),
+        ]);
+
+        let s = &self.score;
+        let mut score = vec![s.points, s.lines, s.level];
+        score.extend(s.clears);
+        score.extend(s.clear_points);
+        score.extend([
+            s.garbage_lines,
+            s.spins,
+            s.mini_spins,
+            s.perfect_clears,
+            s.attack,
+            s.combo,
+            s.back_to_back,
+            s.multiplier,
+        ]);
+
+        let s = &self.stats;
+        let stats = [
+            s.pieces,
+            s.lines,
+            s.keys,
+            s.attack,
+            s.max_combo,
+            s.max_back_to_back,
+            s.drought,
+            s.max_drought,
+            s.finesse_faults,
+        ];
+        let counts = s.piece_counts.iter().map(|(_, count)| count);
+
+        let numbers = |numbers: &mut dyn Iterator<Item = &u32>| {
+            let numbers: Vec<String> = numbers.map(|n| n.to_string()).collect();
+            numbers.join(" ")
+        };
+
+        lines.push(format!("score {}", numbers(&mut score.iter())));
+        lines.push(format!("stats {}", numbers(&mut stats.iter())));
+        lines.push(format!("counts {}", numbers(&mut counts.into_iter())));
+
+        for (name, count) in s.clears.iter() {
+            lines.push(format!("cleared {count} {name}"));
+        }
+
+        if let Some(garbage) = &self.garbage {
+            lines.push(format!(
+                "garbage {} {}",
+                garbage.left,
+                optional(garbage.last_hole, |h| { h.to_string() })
+            ));
+        }
+
+        if let Some(master) = &self.master {
+            let sections: Vec<String> = master.sections.iter().map(|&t| nanos(t)).collect();
+
+            lines.push(format!(
+                "master {} {} {} {}",
+                master.grade,
+                master.gm_eligible,
+                master.checks_passed,
+                sections.join(" ")
+            ));
+        }
+
+        if let Some(drill) = &self.drill {
+            let target = optional(drill.target, |t| {
+                format!("{} {} {}", t.orientation, t.origin[0], t.origin[1])
+            });
+
+            lines.push(format!("drill {} {target}", drill.streak));
+        }
+
+        if let Some(sequence) = &self.sequence {
+            lines.push(format!("sequence {}", variants(sequence)));
+        }
+
+        let last_clear = optional(self.last_clear, |c| {
+            format!("{} {} {}", c.lines, c.perfect_clear, optional(c.spin, spin_text))
+        });
+        let [r0, r1, r2, r3] = self.rng.state;
+
+        lines.extend([
+            format!("queue {}", variants(&self.queue)),
+            format!("history {}", variants(&self.history)),
+            format!("hold {}", optional(self.hold, |v| v.0.to_string())),
+            format!("can_hold {}", self.can_hold),
+            format!("last_clear {last_clear}"),
+            format!("perfect_clear_at {}", optional(self.perfect_clear_at, nanos)),
+            format!("piece_keys {}", self.piece_keys),
+            format!("fault_at {}", optional(self.fault_at, nanos)),
+            format!("last_rotated {}", self.last_rotated),
+            format!("last_kick {} {}", self.last_kick[0], self.last_kick[1]),
+            format!("spin {}", optional(self.spin, spin_text)),
+            format!("rng {r0} {r1} {r2} {r3}"),
+        ]);
+
+        lines
+            .iter()
+            .map(|line| format!("{}\n", line.trim_end()))
+            .collect()
+    }
+
+    /// the state `to_text` wrote, for a game with these settings
+    pub fn parse(text: &str, cfg: &Settings) -> Result<Self, Error> {
+        let mut state = Self::new(cfg, 0);
+        state.minos = [[None; 10]; 20];
+
+        let (columns, rows) = cfg.size();
+        let scale = cfg.scale() as i32;
+        let on_board = |[c, r]: [i32; 2]| (0..columns).contains(&c) && (0..rows).contains(&r);
+
+        for line in text.lines().map(str::trim) {
+            if line.is_empty() {
+                continue;
+            }
+
+            let mut values = Values::new(line);
+            let field = values.word()?;
+            let v = &mut values;
+
+            // for fields of the mode, which the settings say whether there are
+            let missing = || Error::Config(format!("`{line}` doesn't fit the mode."));
+
+            match field {
+                "mino" => {
+                    let [column, row]: [usize; 2] = [v.value()?, v.value()?];
+
+                    if !on_board([column as i32 / scale, row as i32 / scale]) {
+                        return Err(Error::Config(format!("`{line}` is off the board.")));
+                    }
+
+                    state.minos[row][column] = Some(Mino {
+                        locked: v.value()?,
+                        garbage: v.value()?,
+                        locked_at: v.duration()?,
+                        color: [v.value()?, v.value()?, v.value()?],
+                    });
+                },
+                "focused" => {
+                    state.focused_shape = v.optional(|v| {
+                        let variant = v.variant(cfg)?;
+                        let orientation: usize = v.value()?;
+                        let origin = [v.value()?, v.value()?];
+
+                        let piece = &cfg.pieces[variant];
+                        let cells: Vec<[i32; 2]> =
+                            piece.cells(orientation.min(3), origin).collect();
+
+                        if orientation > 3 || !cells.iter().all(|&pos| on_board(pos)) {
+                            return Err(Error::Config(format!("`{line}` is off the board.")));
+                        }
+
+                        Ok(Shape {
+                            variant,
+                            mino_pos: cells
+                                .iter()
+                                .map(|&[c, r]| [c as usize, r as usize])
+                                .collect(),
+                            orientation,
+                            origin,
+                        })
+                    })?
+                },
+                "elapsed" => state.elapsed = v.duration()?,
+                "tick" => state.tick = v.value()?,
+                "phase" => {
+                    state.phase = match v.word()? {
+                        "falling" => Phase::Falling,
+                        "locking" => Phase::Locking(v.value()?),
+                        "clearing" => Phase::LineClear(v.value()?),
+                        "entry" => Phase::Entry(v.value()?),
+                        phase => return Err(Error::Config(format!("Unknown phase `{phase}`."))),
+                    }
+                },
+                "clearing" => {
+                    state.clearing = v.rest(|v| v.value())?;
+
+                    if state.clearing.iter().any(|&r| r >= 20) {
+                        return Err(Error::Config(format!("`{line}` is off the board.")));
+                    }
+                },
+                "fall" => state.fall = v.value()?,
+                "buffered" => {
+                    state.buffered = v.rest(|v| match Event::parse(&[v.word()?])? {
+                        Event::Input(input) => Ok(input),
+                        _ => Err(Error::Config(format!("`{line}` isn't made of moves."))),
+                    })?
+                },
+                "held" => {
+                    let words: Vec<&str> = line.split_whitespace().collect();
+
+                    if let Event::Held(held) = Event::parse(&words)? {
+                        state.held = held;
+                    }
+                },
+                "outcome" => state.outcome = v.optional(|v| Outcome::from_name(v.word()?))?,
+                "score" => {
+                    let s = &mut state.score;
+
+                    s.points = v.value()?;
+                    s.lines = v.value()?;
+                    s.level = v.value()?;
+
+                    for clears in s.clears.iter_mut() {
+                        *clears = v.value()?;
+                    }
+
+                    for points in s.clear_points.iter_mut() {
+                        *points = v.value()?;
+                    }
+
+                    s.garbage_lines = v.value()?;
+                    s.spins = v.value()?;
+                    s.mini_spins = v.value()?;
+                    s.perfect_clears = v.value()?;
+                    s.attack = v.value()?;
+                    s.combo = v.value()?;
+                    s.back_to_back = v.value()?;
+                    s.multiplier = v.value()?;
+                },
+                "stats" => {
+                    let s = &mut state.stats;
+
+                    s.pieces = v.value()?;
+                    s.lines = v.value()?;
+                    s.keys = v.value()?;
+                    s.attack = v.value()?;
+                    s.max_combo = v.value()?;
+                    s.max_back_to_back = v.value()?;
+                    s.drought = v.value()?;
+                    s.max_drought = v.value()?;
+                    s.finesse_faults = v.value()?;
+                },
+                "counts" => {
+                    let counts: Vec<u32> = v.rest(|v| v.value())?;
+
+                    if counts.len() != state.stats.piece_counts.len() {
+                        return Err(Error::Config(format!(
+                            "`{line}` doesn't count every piece of the set."
+                        )));
+                    }
+
+                    for ((_, count), saved) in state.stats.piece_counts.iter_mut().zip(counts) {
+                        *count = saved;
+                    }
+                },
+                "cleared" => {
+                    let count = v.value()?;
+                    let name = v.rest(|v| v.word())?.join(" ");
+
+                    state.stats.clears.insert(name, count);
+                },
+                "garbage" => {
+                    let garbage = state.garbage.as_mut().ok_or_else(missing)?;
+
+                    garbage.left = v.value()?;
+                    garbage.last_hole = v.optional(|v| v.value())?;
+
+                    if garbage
+                        .last_hole
+                        .map(|h| h >= columns as usize)
+                        .unwrap_or(false)
+                    {
+                        return Err(Error::Config(format!("`{line}` is off the board.")));
+                    }
+                },
+                "master" => {
+                    let master = state.master.as_mut().ok_or_else(missing)?;
+
+                    master.grade = v.value()?;
+                    master.gm_eligible = v.value()?;
+                    master.checks_passed = v.value()?;
+                    master.sections = v.rest(|v| v.duration())?;
+
+                    if master.grade >= master::GRADES.len() {
+                        return Err(Error::Config(format!("There's no grade {}.", master.grade)));
+                    }
+                },
+                "drill" => {
+                    let drill = state.drill.as_mut().ok_or_else(missing)?;
+
+                    drill.streak = v.value()?;
+                    drill.target = v.optional(|v| {
+                        Ok(Target {
+                            orientation: v.value()?,
+                            origin: [v.value()?, v.value()?],
+                        })
+                    })?;
+
+                    // checked against the focused shape once the whole state is read
+                },
+                "sequence" => state.sequence = Some(v.rest(|v| v.variant(cfg))?.into()),
+                "queue" => state.queue = v.rest(|v| v.variant(cfg))?.into(),
+                "history" => state.history = v.rest(|v| v.variant(cfg))?.into(),
+                "hold" => state.hold = v.optional(|v| v.variant(cfg))?,
+                "can_hold" => state.can_hold = v.value()?,
+                "last_clear" => {
+                    state.last_clear = v.optional(|v| {
+                        Ok(LineClear {
+                            lines: v.value()?,
+                            perfect_clear: v.value()?,
+                            spin: v.optional(Values::spin)?,
+                        })
+                    })?
+                },
+                "perfect_clear_at" => state.perfect_clear_at = v.optional(Values::duration)?,
+                "piece_keys" => state.piece_keys = v.value()?,
+                "fault_at" => state.fault_at = v.optional(Values::duration)?,
+                "last_rotated" => state.last_rotated = v.value()?,
+                "last_kick" => state.last_kick = [v.value()?, v.value()?],
+                "spin" => state.spin = v.optional(Values::spin)?,
+                "rng" => state.rng.state = [v.value()?, v.value()?, v.value()?, v.value()?],
+                _ => return Err(Error::Config(format!("Unknown field in `{line}`."))),
+            }
+        }
+
+        // the drill target is drawn under the shape it's for
+        if let (Some(m), Some(target)) =
+            (&state.focused_shape, state.drill.as_ref().and_then(|d| d.target))
+        {
+            let piece = &cfg.pieces[m.variant];
+
+            if target.orientation > 3
+                || !piece.cells(target.orientation, target.origin).all(on_board)
+            {
+                return Err(Error::Config("The drill target is off the board.".into()));
+            }
+        }
+
+        Ok(state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use crate::message::MinoDirection;
+    use crate::message::Rotation;
+
+    use super::super::replay::Verdict;
+    use super::super::Tetris;
+    use super::*;
+
+    fn play(game: &mut Tetris, frames: u32) {
+        for frame in 0..frames {
+            if game.is_over() {
+                return;
+            }
+
+            match frame % 50 {
+                5 => game.input(Input::Turn(MinoDirection::Left)),
+                15 => game.input(Input::Rotate(Rotation::Clockwise)),
+                25 => game.input(Input::Hold),
+                _ => (),
+            }
+
+            game.step();
+        }
+    }
+
+    #[test]
+    fn resumes_where_it_was_left() {
+        for mode in ["marathon", "dig", "master", "drill", "practice"] {
+            let args = ["--mode", mode].map(String::from).to_vec();
+            let mut game = Tetris::with_seed(Settings::from_args(args.into_iter()).unwrap(), 3);
+            let path = env::temp_dir().join(format!("tetris-save-{mode}.txt"));
+
+            play(&mut game, 600);
+            game.save(&path).unwrap();
+
+            let mut resumed = Tetris::resume(&path).unwrap();
+
+            assert_eq!(resumed.state.to_text(), game.state.to_text(), "{mode}");
+
+            // and goes on the same way, for the replay to stay valid
+            play(&mut game, 600);
+            play(&mut resumed, 600);
+
+            assert_eq!(resumed.state.to_text(), game.state.to_text(), "{mode}");
+
+            let replay = resumed.replay();
+            assert_eq!(
+                replay.verify().unwrap(),
+                Verdict::Valid(replay.end.unwrap()),
+                "{mode}"
+            );
+        }
+    }
+
+    #[test]
+    fn refuses_shapes_off_the_board() {
+        let cfg = Settings::default();
+
+        assert!(State::parse("mino 10 0 true false 0 0 0 0", &cfg).is_err());
+        assert!(State::parse("focused 0 0 9 0", &cfg).is_err());
+        assert!(State::parse("queue 0 99", &cfg).is_err());
+        assert!(State::parse("garbage 3 -", &cfg).is_err());
+    }
+}
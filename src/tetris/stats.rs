@@ -0,0 +1,141 @@
+use std::collections::BTreeMap;
+use std::time::Duration;
+use std::fmt;
+
+use super::pieces::PieceSet;
+use super::mino::ShapeVariant;
+use super::score::LineClear;
+use super::score::Score;
+
+#[derive(Debug, Default, Clone)]
+pub struct Stats {
+    /// shapes locked into the stack
+    pub pieces: u32,
+    /// shapes locked of each piece in the set, in its order
+    pub piece_counts: Vec<(char, u32)>,
+    pub lines: u32,
+    /// clears by their name, e.g `t-spin mini single`
+    pub clears: BTreeMap<String, u32>,
+    /// keys pressed, not counting repeats of a held key
+    pub keys: u32,
+    pub attack: u32,
+    /// most clears in a row after the first one
+    pub max_combo: u32,
+    /// most difficult clears in a row after the first one
+    pub max_back_to_back: u32,
+    /// shapes locked since the last i
+    pub drought: u32,
+    pub max_drought: u32,
+    /// shapes placed with more keys than needed
+    pub finesse_faults: u32,
+}
+
+impl Stats {
+    pub fn new(pieces: &PieceSet) -> Self {
+        Self {
+            piece_counts: pieces.pieces.iter().map(|p| (p.name, 0)).collect(),
+            ..Default::default()
+        }
+    }
+
+    pub fn locked(&mut self, variant: ShapeVariant) {
+        self.pieces += 1;
+
+        let (name, count) = &mut self.piece_counts[variant.0];
+        *count += 1;
+
+        self.drought = match name {
+            'I' => 0,
+            _ => self.drought + 1,
+        };
+        self.max_drought = self.max_drought.max(self.drought);
+    }
+
+    /// with the score as it is after the clear
+    pub fn cleared(&mut self, clear: LineClear, score: &Score) {
+        self.lines += clear.lines as u32;
+        self.attack = score.attack;
+        self.max_combo = self.max_combo.max(score.combo.saturating_sub(1));
+        self.max_back_to_back = self
+            .max_back_to_back
+            .max(score.back_to_back.saturating_sub(1));
+
+        *self.clears.entry(clear.name()).or_default() += 1;
+
+        if clear.perfect_clear {
+            *self.clears.entry("perfect clear".into()).or_default() += 1;
+        }
+    }
+
+    /// pieces per second
+    pub fn pps(&self, elapsed: Duration) -> f32 {
+        self.pieces as f32 / elapsed.as_secs_f32().max(1.0)
+    }
+
+    /// keys per piece
+    pub fn kpp(&self) -> f32 {
+        self.keys as f32 / self.pieces.max(1) as f32
+    }
+
+    /// attack per minute
+    pub fn apm(&self, elapsed: Duration) -> f32 {
+        self.attack as f32 * 60.0 / elapsed.as_secs_f32().max(1.0)
+    }
+
+    /// the numbers of the side panel by their label, then the count of every kind of clear
+    pub fn panel(&self, elapsed: Duration) -> Vec<(String, String)> {
+        let mut panel = vec![
+            ("pps".to_string(), format!("{:.2}", self.pps(elapsed))),
+            ("kpp".to_string(), format!("{:.2}", self.kpp())),
+            ("apm".to_string(), format!("{:.1}", self.apm(elapsed))),
+            ("lines".to_string(), self.lines.to_string()),
+            ("combo".to_string(), self.max_combo.to_string()),
+            ("b2b".to_string(), self.max_back_to_back.to_string()),
+            ("faults".to_string(), self.finesse_faults.to_string()),
+        ];
+
+        for (name, count) in self.clears.iter() {
+            panel.push((name.clone(), count.to_string()));
+        }
+
+        panel
+    }
+
+    pub fn display(&self, elapsed: Duration) -> impl fmt::Display + '_ {
+        StatsDisplay {
+            stats: self,
+            elapsed,
+        }
+    }
+}
+
+struct StatsDisplay<'a> {
+    stats: &'a Stats,
+    elapsed: Duration,
+}
+
+impl fmt::Display for StatsDisplay<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (stats, elapsed) = (self.stats, self.elapsed);
+
+        writeln!(f, "  pieces: {:>4} ({:.2}/s)", stats.pieces, stats.pps(elapsed))?;
+        writeln!(f, "    keys: {:>4} ({:.2}/piece)", stats.keys, stats.kpp())?;
+        writeln!(f, "  attack: {:>4} ({:.1}/min)", stats.attack, stats.apm(elapsed))?;
+        writeln!(f, "   combo: {:>4} at most", stats.max_combo)?;
+        writeln!(f, "     b2b: {:>4} at most", stats.max_back_to_back)?;
+        writeln!(f, " drought: {:>4} at most", stats.max_drought)?;
+        writeln!(f, "  faults: {:>4}", stats.finesse_faults)?;
+
+        for (name, count) in stats.clears.iter() {
+            writeln!(f, "{count:>5} x {name}")?;
+        }
+
+        let counts: Vec<String> = stats
+            .piece_counts
+            .iter()
+            .map(|(name, count)| format!("{name} {count}"))
+            .collect();
+
+        write!(f, "{}", counts.join(", "))
+    }
+}
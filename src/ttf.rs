@@ -0,0 +1,166 @@
+extern crate sdl2;
+use sdl2::render::TextureCreator;
+use sdl2::render::WindowCanvas;
+use sdl2::render::Texture;
+use sdl2::video::WindowContext;
+use sdl2::ttf::Sdl2TtfContext;
+use sdl2::ttf::Font;
+use sdl2::rwops::RWops;
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+
+use std::collections::HashMap;
+
+/// DejaVu Sans Mono, for when no font is given or the one given can't be loaded
+const FALLBACK: &[u8] = include_bytes!("../assets/DejaVuSansMono.ttf");
+
+/// font sizes kept at once, resizing the window goes through a lot of them
+const SIZES_KEPT: usize = 4;
+
+/// where the x given to `Text::draw` is on the line
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Align {
+    Left,
+    Center,
+    Right,
+}
+
+struct Glyph<'t> {
+    /// white, tinted when drawn. `None` for glyphs with nothing to draw, e.g a space
+    texture: Option<Texture<'t>>,
+    /// how far the next glyph goes to the right
+    advance: i32,
+}
+
+/// fonts by size and every glyph drawn so far, so text is rendered by sdl_ttf only once
+pub struct Text<'t> {
+    ttf: &'t Sdl2TtfContext,
+    creator: &'t TextureCreator<WindowContext>,
+    /// the font file, `None` for the bundled one
+    path: Option<String>,
+    fonts: HashMap<u16, Font<'t, 'static>>,
+    glyphs: HashMap<(char, u16), Glyph<'t>>,
+}
+
+impl<'t> Text<'t> {
+    /// with the font at `path`, or the bundled one if there's none or it can't be loaded
+    pub fn new(
+        ttf: &'t Sdl2TtfContext,
+        creator: &'t TextureCreator<WindowContext>,
+        path: Option<&str>,
+    ) -> Self {
+        let path = path.and_then(|path| match ttf.load_font(path, 16) {
+            Ok(_) => Some(path.to_string()),
+            Err(e) => {
+                eprintln!("Couldn't load the font {path}, using the bundled one: {e}");
+                None
+            },
+        });
+
+        Self {
+            ttf,
+            creator,
+            path,
+            fonts: HashMap::new(),
+            glyphs: HashMap::new(),
+        }
+    }
+
+    fn font(&mut self, size: u16) -> Result<&Font<'t, 'static>, String> {
+        if !self.fonts.contains_key(&size) {
+            if self.fonts.len() >= SIZES_KEPT {
+                self.fonts.clear();
+                self.glyphs.clear();
+            }
+
+            let font = match &self.path {
+                Some(path) => self.ttf.load_font(path, size)?,
+                None => self
+                    .ttf
+                    .load_font_from_rwops(RWops::from_bytes(FALLBACK)?, size)?,
+            };
+
+            self.fonts.insert(size, font);
+        }
+
+        Ok(&self.fonts[&size])
+    }
+
+    fn glyph(&mut self, c: char, size: u16) -> Result<&mut Glyph<'t>, String> {
+        if !self.glyphs.contains_key(&(c, size)) {
+            let creator = self.creator;
+            let font = self.font(size)?;
+
+            let advance = font
+                .find_glyph_metrics(c)
+                .map(|metrics| metrics.advance)
+                .unwrap_or(0);
+
+            // sdl_ttf refuses to render glyphs without any width
+            let texture = match font.render_char(c).blended(Color::WHITE) {
+                Ok(surface) => Some(
+                    creator
+                        .create_texture_from_surface(surface)
+                        .map_err(|e| e.to_string())?,
+                ),
+                Err(_) => None,
+            };
+
+            self.glyphs.insert((c, size), Glyph { texture, advance });
+        }
+
+        self.glyphs
+            .get_mut(&(c, size))
+            .ok_or(format!("Couldn't cache the glyph `{c}`."))
+    }
+
+    /// the width and height of `text` as drawn at `size`
+    pub fn size_of(&mut self, text: &str, size: u16) -> Result<(u32, u32), String> {
+        let mut width = 0;
+
+        for c in text.chars() {
+            width += self.glyph(c, size)?.advance;
+        }
+
+        let height = self.font(size)?.height();
+
+        Ok((width.max(0) as u32, height.max(0) as u32))
+    }
+
+    /// a line of `text` with its top at `y`, returns where it was drawn
+    pub fn draw(
+        &mut self,
+        canvas: &mut WindowCanvas,
+        text: &str,
+        (x, y): (i32, i32),
+        size: u16,
+        color: Color,
+        align: Align,
+    ) -> Result<Rect, String> {
+        let (width, height) = self.size_of(text, size)?;
+
+        let left = match align {
+            Align::Left => x,
+            Align::Center => x - width as i32 / 2,
+            Align::Right => x - width as i32,
+        };
+
+        let mut pen = left;
+
+        for c in text.chars() {
+            let glyph = self.glyph(c, size)?;
+
+            if let Some(texture) = glyph.texture.as_mut() {
+                let query = texture.query();
+
+                texture.set_color_mod(color.r, color.g, color.b);
+                texture.set_alpha_mod(color.a);
+                canvas.copy(texture, None, Rect::new(pen, y, query.width, query.height))?;
+            }
+
+            pen += glyph.advance;
+        }
+
+        Ok(Rect::new(left, y, width.max(1), height.max(1)))
+    }
+}